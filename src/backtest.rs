@@ -0,0 +1,233 @@
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+use futures::Future;
+use the_blue_alliance::event::Event;
+use the_blue_alliance::matches::Match;
+use elo::{self, Elo, EloConfig};
+use predict::{Model, Predictor};
+use state::{self, Participation};
+
+/// How many equal-width bins predictions are grouped into for calibration.
+const BINS: usize = 10;
+
+/// Predictions that fell into one calibration bin.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Bin {
+    pub matches: usize,
+    predicted: f64,
+    observed: f64,
+}
+
+impl Bin {
+    /// The average predicted chance of red winning.
+    pub fn predicted(&self) -> f64 {
+        self.predicted / self.matches as f64
+    }
+
+    /// How often red actually won, with ties counting as half.
+    pub fn observed(&self) -> f64 {
+        self.observed / self.matches as f64
+    }
+}
+
+/// How well one model predicted a set of matches.
+#[derive(Debug, Clone, Default)]
+pub struct Score {
+    pub matches: usize,
+    brier: f64,
+    correct: usize,
+    decided: usize,
+    bins: [Bin; BINS],
+}
+
+impl Score {
+    fn add(&mut self, red_win: f64, outcome: f64) {
+        self.matches += 1;
+        self.brier += (red_win - outcome) * (red_win - outcome);
+        if red_win != 0.5 && outcome != 0.5 {
+            self.decided += 1;
+            if (red_win > 0.5) == (outcome > 0.5) {
+                self.correct += 1;
+            }
+        }
+        let bin = &mut self.bins[std::cmp::min((red_win * BINS as f64) as usize, BINS - 1)];
+        bin.matches += 1;
+        bin.predicted += red_win;
+        bin.observed += outcome;
+    }
+
+    fn merge(&mut self, other: &Score) {
+        self.matches += other.matches;
+        self.brier += other.brier;
+        self.correct += other.correct;
+        self.decided += other.decided;
+        for (bin, other) in self.bins.iter_mut().zip(other.bins.iter()) {
+            bin.matches += other.matches;
+            bin.predicted += other.predicted;
+            bin.observed += other.observed;
+        }
+    }
+
+    /// The mean squared error of the predicted chance of red winning, if anything was
+    /// predicted. Always guessing 50% scores 0.25.
+    pub fn brier(&self) -> Option<f64> {
+        if self.matches == 0 {
+            None
+        } else {
+            Some(self.brier / self.matches as f64)
+        }
+    }
+
+    /// How often the favorite won, leaving out ties and toss-ups, if any match had a favorite
+    /// and a winner.
+    pub fn accuracy(&self) -> Option<f64> {
+        if self.decided == 0 {
+            None
+        } else {
+            Some(self.correct as f64 / self.decided as f64)
+        }
+    }
+
+    /// The non-empty calibration bins, with the lowest prediction each one covers.
+    pub fn calibration(&self) -> Vec<(f64, Bin)> {
+        self.bins.iter().enumerate()
+            .filter(|(_, b)| b.matches > 0)
+            .map(|(i, &b)| (i as f64 / BINS as f64, b))
+            .collect()
+    }
+}
+
+/// Replays an event's matches in the order they were played, predicting each one from only the
+/// matches before it. Times can be missing or shared, so matches are replayed in play order
+/// rather than by time. `elo` should hold the ratings from before the event.
+pub fn backtest_event(year: i32, matches: &[Match], participation: Participation, strength: Option<f64>, models: &[Model], elo: Option<&Elo>) -> Vec<(Model, Score)> {
+    let mut matches: Vec<&Match> = matches.iter().filter(|m| elo::red_outcome(m).is_some()).collect();
+    matches.sort_by_key(|m| m.play_order());
+
+    let mut scores: Vec<(Model, Score)> = models.iter().map(|&m| (m, Score::default())).collect();
+    for (i, m) in matches.iter().enumerate() {
+        let played: Vec<Match> = matches[..i].iter().map(|&p| p.clone()).collect();
+        let elo = elo.map(|elo| {
+            let mut elo = elo.clone();
+            elo.add_matches(played.clone());
+            elo
+        });
+        let predictor = Predictor::new(year, &played, participation, strength, elo);
+        let outcome = elo::red_outcome(m).unwrap();
+
+        for (model, score) in scores.iter_mut() {
+            if let Some(prediction) = predictor.predict(m, *model) {
+                score.add(prediction.red_win, outcome);
+            }
+        }
+    }
+    scores
+}
+
+/// Takes the matches from `pending`, each with its event's start and end dates, that had been
+/// played by the start of an event starting on `start`: those played before the day it started,
+/// and every match of an event that had already ended. Only events that started on an earlier
+/// day count, since the times of matches on the same day can't be compared across time zones.
+fn take_played_before(pending: &mut Vec<((NaiveDate, NaiveDate), &Match)>, start: NaiveDate) -> Vec<Match> {
+    let cutoff = Utc.from_utc_date(&start).and_hms(0, 0, 0);
+    let (before, after) = pending.drain(..)
+        .partition(|&((event_start, event_end), m)| event_start < start && (event_end < start || state::played_before(m, cutoff)));
+    *pending = after;
+    before.into_iter().map(|(_, m)| m.clone()).collect()
+}
+
+/// Backtests every official event in `year` that finished before `time`, in the order they
+/// started. Elo ratings carry over from the previous season, and before each event take in
+/// the matches of the season played before it started.
+pub fn backtest_season(tba: &the_blue_alliance::TBA, year: i32, time: DateTime<Utc>, participation: Participation, strength: Option<f64>, models: &[Model]) -> Vec<(Model, Score)> {
+    let mut events: Vec<Event> = Event::in_year(tba, year).wait().unwrap()
+        .into_iter()
+        .filter(|e| e.is_official() && Utc.from_utc_date(&e.end_date) < time.date())
+        .collect();
+    events.sort_unstable_by_key(|e| e.start_date);
+    let event_matches = futures::future::join_all(events.iter().map(|e| e.matches(tba))).wait().unwrap();
+
+    let mut elo = if models.contains(&Model::Elo) {
+        Some(elo::ratings_at(tba, &[year - 1], time, EloConfig::default(), participation, None))
+    } else {
+        None
+    };
+    let mut pending: Vec<((NaiveDate, NaiveDate), &Match)> = events.iter().zip(event_matches.iter())
+        .flat_map(|(e, matches)| matches.iter().map(move |m| ((e.start_date, e.end_date), m)))
+        .collect();
+
+    let mut scores: Vec<(Model, Score)> = models.iter().map(|&m| (m, Score::default())).collect();
+    for (event, matches) in events.iter().zip(event_matches.iter()) {
+        debug!("Backtesting {}", event.key);
+        if let Some(ref mut elo) = elo {
+            elo.add_matches(take_played_before(&mut pending, event.start_date));
+        }
+        for ((_, total), (_, score)) in scores.iter_mut().zip(backtest_event(year, matches, participation, strength, models, elo.as_ref())) {
+            total.merge(&score);
+        }
+    }
+    scores
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use testing::{qual_2019, qual_at};
+
+    #[test]
+    fn empty_scores_have_no_brier_or_accuracy() {
+        let mut score = Score::default();
+        assert_eq!(score.brier(), None);
+        assert_eq!(score.accuracy(), None);
+
+        score.add(0.5, 1.0);
+        assert_eq!(score.brier(), Some(0.25));
+        assert_eq!(score.accuracy(), None);
+
+        score.add(0.8, 1.0);
+        assert_eq!(score.accuracy(), Some(1.0));
+    }
+
+    #[test]
+    fn untimed_matches_do_not_leak_into_earlier_predictions() {
+        let mut matches = vec![
+            qual_2019(1, [1, 2, 3], [4, 5, 6], [24, 12, 15, 6, 3], [15, 10, 9, 6, 0]),
+            qual_2019(2, [1, 4, 5], [2, 3, 6], [18, 14, 12, 6, 0], [21, 8, 6, 3, 5]),
+            qual_2019(3, [1, 2, 6], [3, 4, 5], [21, 10, 12, 9, 3], [18, 12, 15, 3, 0]),
+        ];
+        matches[2].time = None;
+        matches[2].actual_time = None;
+
+        let scores = backtest_event(2019, &matches, Participation::default(), None, &[Model::Opr], None);
+        // The first match has nothing before it to solve OPRs from.
+        assert_eq!(scores[0].1.matches, 2);
+    }
+
+    #[test]
+    fn elo_only_sees_matches_before_the_event_started() {
+        let date = |day| NaiveDate::from_ymd(2019, 3, day);
+        let noon = |day| Utc.ymd(2019, 3, day).and_hms(12, 0, 0).timestamp() as u64;
+        // An event from the 1st to the 3rd, one that overlaps the next from the 7th to the 9th,
+        // and one starting the same day as the next, whose first match is already on the 8th
+        // local time but still the 7th in UTC.
+        let early = qual_at("2019early", 1, [1, 2, 3], [4, 5, 6], (10, 5), noon(2));
+        let mut untimed = qual_at("2019early", 2, [1, 2, 3], [4, 5, 6], (10, 5), noon(2));
+        untimed.time = None;
+        untimed.actual_time = None;
+        let overlap_before = qual_at("2019overlap", 1, [1, 2, 3], [4, 5, 6], (10, 5), noon(7));
+        let overlap_during = qual_at("2019overlap", 2, [1, 2, 3], [4, 5, 6], (10, 5), noon(9));
+        let same_day = qual_at("2019same", 1, [1, 2, 3], [4, 5, 6], (10, 5), noon(7) + 11 * 3600 - 600);
+
+        let mut pending = vec![
+            ((date(1), date(3)), &early),
+            ((date(1), date(3)), &untimed),
+            ((date(7), date(9)), &overlap_before),
+            ((date(7), date(9)), &overlap_during),
+            ((date(8), date(10)), &same_day),
+        ];
+        let keys = |matches: Vec<Match>| matches.into_iter().map(|m| m.key.to_string()).collect::<Vec<_>>();
+        assert_eq!(keys(take_played_before(&mut pending, date(8))), ["2019early_qm1", "2019early_qm2", "2019overlap_qm1"]);
+        assert!(take_played_before(&mut pending, date(8)).is_empty());
+        assert_eq!(keys(take_played_before(&mut pending, date(15))), ["2019overlap_qm2", "2019same_qm1"]);
+        assert!(pending.is_empty());
+    }
+}
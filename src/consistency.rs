@@ -0,0 +1,170 @@
+use std::collections::HashMap;
+use the_blue_alliance::keys::TeamKey;
+use the_blue_alliance::matches::{AllianceColor, Match};
+use opr::{Component, ComponentTable};
+use state::Participation;
+
+/// One team's contributions to a component in each match it played, in the order played.
+///
+/// A team's contribution to a match is what its alliance scored less its partners' OPRs, which
+/// is the team's own OPR plus whatever the alliance scored above or below its prediction.
+#[derive(Debug, Clone)]
+pub struct Contributions {
+    pub team: TeamKey,
+    pub values: Vec<f64>,
+}
+
+impl Contributions {
+    pub fn mean(&self) -> f64 {
+        self.values.iter().sum::<f64>() / self.values.len() as f64
+    }
+
+    /// The sample standard deviation, or `None` with fewer than two matches.
+    pub fn standard_deviation(&self) -> Option<f64> {
+        if self.values.len() < 2 {
+            return None;
+        }
+        let mean = self.mean();
+        let variance = self.values.iter().map(|v| (v - mean) * (v - mean)).sum::<f64>() / (self.values.len() - 1) as f64;
+        Some(variance.sqrt())
+    }
+
+    pub fn min(&self) -> f64 {
+        self.values.iter().cloned().fold(f64::INFINITY, f64::min)
+    }
+
+    pub fn max(&self) -> f64 {
+        self.values.iter().cloned().fold(f64::NEG_INFINITY, f64::max)
+    }
+
+    /// The value `p` of the way from the lowest contribution to the highest, interpolating
+    /// between matches.
+    pub fn percentile(&self, p: f64) -> f64 {
+        let mut sorted = self.values.clone();
+        sorted.sort_unstable_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+        let position = p.clamp(0.0, 1.0) * (sorted.len() - 1) as f64;
+        let low = position.floor() as usize;
+        let high = position.ceil() as usize;
+        sorted[low] + (sorted[high] - sorted[low]) * (position - low as f64)
+    }
+
+    /// How much the team's contribution changed per match over the event, by least squares, or
+    /// `None` with fewer than two matches.
+    pub fn trend(&self) -> Option<f64> {
+        slope(&self.values)
+    }
+}
+
+/// The least squares slope of `values` against their index, or `None` with fewer than two values.
+pub fn slope(values: &[f64]) -> Option<f64> {
+    if values.len() < 2 {
+        return None;
+    }
+    let mean_x = (values.len() - 1) as f64 / 2.0;
+    let mean_y = values.iter().sum::<f64>() / values.len() as f64;
+    let (covariance, variance) = values.iter().enumerate()
+        .map(|(x, y)| (x as f64 - mean_x, y - mean_y))
+        .fold((0.0, 0.0), |(c, v), (dx, dy)| (c + dx * dy, v + dx * dx));
+    Some(covariance / variance)
+}
+
+/// Attributes `component` in every played match to the teams on each alliance, using the
+/// partners' values in column `column` of `oprs`. Teams are returned best average first.
+pub fn contributions(matches: &[Match], oprs: &ComponentTable, component: &Component, column: usize, participation: Participation) -> Vec<Contributions> {
+    let mut matches: Vec<&Match> = matches.iter().collect();
+    matches.sort_unstable_by(|a, b| a.cmp_by_time(b));
+
+    let mut teams: HashMap<TeamKey, Vec<f64>> = HashMap::new();
+    for m in matches {
+        let alliances = match m.alliances {
+            Some(ref alliances) => alliances,
+            None => continue,
+        };
+        for &color in &AllianceColor::BOTH {
+            let value = match (component.metric)(m, color) {
+                Some(value) => value,
+                None => continue,
+            };
+            let alliance = alliances.get(color);
+            for t in alliance.team_keys.iter().filter(|t| participation.is_counted(alliance, t)) {
+                let partners: Option<f64> = alliance.team_keys.iter()
+                    .filter(|p| *p != t)
+                    .map(|p| oprs.value_or_mean(p, column))
+                    .sum();
+                if let Some(partners) = partners {
+                    teams.entry(t.clone()).or_default().push(value - partners);
+                }
+            }
+        }
+    }
+
+    let mut result: Vec<Contributions> = teams.into_iter()
+        .map(|(team, values)| Contributions { team, values })
+        .collect();
+    result.sort_by(|a, b| b.mean().partial_cmp(&a.mean()).unwrap_or(std::cmp::Ordering::Equal));
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opr;
+    use testing::{self, qual};
+
+    fn contributions(values: &[f64]) -> Contributions {
+        Contributions { team: TeamKey::from_number(1), values: values.to_vec() }
+    }
+
+    #[test]
+    fn percentiles_interpolate_between_matches() {
+        let c = contributions(&[40.0, 10.0, 30.0, 20.0]);
+        assert_eq!(c.percentile(0.0), 10.0);
+        assert_eq!(c.percentile(1.0), 40.0);
+        assert_eq!(c.percentile(0.5), 25.0);
+        assert!((c.percentile(0.25) - 17.5).abs() < 1e-9);
+        // Out of range fractions are clamped to the ends.
+        assert_eq!(c.percentile(-1.0), 10.0);
+        assert_eq!(c.percentile(2.0), 40.0);
+        assert_eq!(contributions(&[7.0]).percentile(0.5), 7.0);
+    }
+
+    #[test]
+    fn spread_and_trend_need_two_matches() {
+        let one = contributions(&[12.0]);
+        assert_eq!(one.standard_deviation(), None);
+        assert_eq!(one.trend(), None);
+        assert_eq!(slope(&[]), None);
+
+        let two = contributions(&[10.0, 14.0]);
+        assert!((two.standard_deviation().unwrap() - 8f64.sqrt()).abs() < 1e-9);
+        assert_eq!(two.trend(), Some(4.0));
+    }
+
+    #[test]
+    fn slope_fits_a_line() {
+        assert_eq!(slope(&[1.0, 3.0, 5.0, 7.0]), Some(2.0));
+        assert_eq!(slope(&[5.0, 5.0, 5.0]), Some(0.0));
+        // Uneven steps down, fitted by least squares.
+        assert!((slope(&[10.0, 8.0, 8.0, 6.0]).unwrap() + 1.2).abs() < 1e-9);
+    }
+
+    #[test]
+    fn contributions_subtract_partners_oprs() {
+        let matches = vec![
+            qual(1, [1, 2, 3], [4, 5, 6], (60, 45)),
+            qual(2, [1, 4, 5], [2, 3, 6], (50, 55)),
+        ];
+        let oprs = testing::oprs(vec!["OPR"], &[
+            (1, &[30.0]), (2, &[20.0]), (3, &[10.0]),
+            (4, &[15.0]), (5, &[15.0]), (6, &[15.0]),
+        ]);
+        let component = opr::components(2019).remove(0);
+        let result = super::contributions(&matches, &oprs, &component, 0, Participation::default());
+
+        let team = |n| result.iter().find(|c| c.team == TeamKey::from_number(n)).unwrap();
+        // 60 - (20 + 10) in the first match, then 50 - (15 + 15) in the second.
+        assert_eq!(team(1).values, vec![30.0, 20.0]);
+        assert_eq!(team(6).values, vec![15.0, 25.0]);
+        assert!(result.windows(2).all(|w| w[0].mean() >= w[1].mean()));
+    }
+}
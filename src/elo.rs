@@ -0,0 +1,224 @@
+use std::collections::HashMap;
+use chrono::{DateTime, Utc};
+use futures::Future;
+use the_blue_alliance::keys::{EventKey, TeamKey};
+use the_blue_alliance::matches::{AllianceColor, CompLevel, Match};
+use state::{self, Participation};
+
+/// The rating every team starts at, and that ratings regress toward between seasons.
+pub const MEAN_RATING: f64 = 1500.0;
+
+/// A difference of this many points between alliances' summed ratings makes the stronger
+/// alliance ten times as likely to win.
+const SCALE: f64 = 400.0;
+
+/// How ratings respond to results.
+#[derive(Debug, Clone, Copy)]
+pub struct EloConfig {
+    /// The most a single qualification match can move a team's rating.
+    pub k: f64,
+    /// Multiplier on `k` for playoff matches, where alliances are not random.
+    pub playoff_weight: f64,
+    /// Fraction of the way each rating is pulled back to `MEAN_RATING` when a new season starts.
+    pub regression: f64,
+}
+
+impl Default for EloConfig {
+    fn default() -> EloConfig {
+        EloConfig {
+            k: 12.0,
+            playoff_weight: 0.5,
+            regression: 0.2,
+        }
+    }
+}
+
+/// Team ratings, updated one match at a time in the order the matches were played.
+///
+/// An alliance's rating is the sum of its teams' ratings, and every counted team on an
+/// alliance gets the same adjustment after a match.
+#[derive(Debug, Clone)]
+pub struct Elo {
+    config: EloConfig,
+    participation: Participation,
+    ratings: HashMap<TeamKey, f64>,
+    year: Option<i32>,
+}
+
+impl Elo {
+    pub fn new(config: EloConfig, participation: Participation) -> Elo {
+        Elo {
+            config,
+            participation,
+            ratings: HashMap::new(),
+            year: None,
+        }
+    }
+
+    /// A team's rating, or `MEAN_RATING` if it has not played yet.
+    pub fn rating(&self, team: &TeamKey) -> f64 {
+        self.ratings.get(team).cloned().unwrap_or(MEAN_RATING)
+    }
+
+    /// Every rated team, best first.
+    pub fn sorted(&self) -> Vec<(&TeamKey, f64)> {
+        let mut ratings: Vec<_> = self.ratings.iter().map(|(t, &r)| (t, r)).collect();
+        ratings.sort_unstable_by(|(_, a), (_, b)| b.partial_cmp(a).unwrap_or(std::cmp::Ordering::Equal));
+        ratings
+    }
+
+    pub fn alliance_rating<'t, I: IntoIterator<Item = &'t TeamKey>>(&self, teams: I) -> f64 {
+        teams.into_iter().map(|t| self.rating(t)).sum()
+    }
+
+    /// The probability that `color` wins the match, which does not need to have been played.
+    pub fn win_probability(&self, m: &Match, color: AllianceColor) -> Option<f64> {
+        let alliances = m.alliances.as_ref()?;
+        let difference = self.alliance_rating(alliances.get(color).team_keys.iter())
+            - self.alliance_rating(alliances.get(color.opponent()).team_keys.iter());
+        Some(1.0 / (1.0 + 10f64.powf(-difference / SCALE)))
+    }
+
+    /// Updates ratings from a played match. Matches must be added in the order they were
+    /// played; the first match of a new season regresses every rating first.
+    pub fn add_match(&mut self, m: &Match) {
+        let (expected, actual) = match (self.win_probability(m, AllianceColor::Red), red_outcome(m)) {
+            (Some(expected), Some(actual)) => (expected, actual),
+            _ => return,
+        };
+
+        let year = m.key.year();
+        if matches!(self.year, Some(y) if y != year) {
+            let regression = self.config.regression;
+            for rating in self.ratings.values_mut() {
+                *rating += (MEAN_RATING - *rating) * regression;
+            }
+        }
+        self.year = Some(year);
+
+        let k = if m.comp_level == CompLevel::QualificationMatch {
+            self.config.k
+        } else {
+            self.config.k * self.config.playoff_weight
+        };
+        let change = k * (actual - expected);
+
+        let alliances = m.alliances.as_ref().unwrap();
+        let participation = self.participation;
+        for &(color, change) in &[(AllianceColor::Red, change), (AllianceColor::Blue, -change)] {
+            let alliance = alliances.get(color);
+            for team in alliance.team_keys.iter().filter(|t| participation.is_counted(alliance, t)) {
+                *self.ratings.entry(team.clone()).or_insert(MEAN_RATING) += change;
+            }
+        }
+    }
+
+    /// Adds matches in the order they were played, by `Match::actual_time`.
+    pub fn add_matches(&mut self, mut matches: Vec<Match>) {
+        matches.sort_unstable_by(|a, b| a.cmp_by_time(b));
+        for m in &matches {
+            self.add_match(m);
+        }
+    }
+
+    /// Adds every match of every official event in `year` played before `time`, except those
+    /// at `exclude`.
+    pub fn add_season(&mut self, tba: &the_blue_alliance::TBA, year: i32, time: DateTime<Utc>, exclude: Option<&EventKey>) {
+        let events: Vec<_> = the_blue_alliance::event::Event::in_year(tba, year).wait().unwrap()
+            .into_iter()
+            .filter(|e| e.is_official() && Some(&e.key) != exclude)
+            .collect();
+        let matches = futures::future::join_all(events.iter().map(|e| e.matches(tba))).wait().unwrap()
+            .into_iter()
+            .flatten()
+            .filter(|m| state::played_before(m, time))
+            .collect();
+        self.add_matches(matches);
+    }
+}
+
+/// How a played match went for red: 1 for a win, 0 for a loss and 0.5 for a tie.
+pub fn red_outcome(m: &Match) -> Option<f64> {
+    let (red, blue) = (m.score_for(AllianceColor::Red)?, m.score_for(AllianceColor::Blue)?);
+    Some(match m.winning_alliance.map(|w| w.color()) {
+        Some(Some(AllianceColor::Red)) => 1.0,
+        Some(Some(AllianceColor::Blue)) => 0.0,
+        Some(None) => 0.5,
+        None if red > blue => 1.0,
+        None if red < blue => 0.0,
+        None => 0.5,
+    })
+}
+
+/// Ratings from every match of every official event in `years` played before `time`,
+/// optionally leaving out one event so its matches can be added as they are played.
+pub fn ratings_at(tba: &the_blue_alliance::TBA, years: &[i32], time: DateTime<Utc>, config: EloConfig, participation: Participation, exclude: Option<&EventKey>) -> Elo {
+    let mut elo = Elo::new(config, participation);
+    for &year in years {
+        elo.add_season(tba, year, time, exclude);
+    }
+    elo
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use testing::{qual, qual_at};
+
+    fn elo() -> Elo {
+        Elo::new(EloConfig::default(), Participation::default())
+    }
+
+    #[test]
+    fn even_alliances_move_by_half_of_k() {
+        let mut elo = elo();
+        elo.add_match(&qual(1, [1, 2, 3], [4, 5, 6], (30, 20)));
+        assert_eq!(elo.rating(&TeamKey::from_number(1)), MEAN_RATING + 6.0);
+        assert_eq!(elo.rating(&TeamKey::from_number(6)), MEAN_RATING - 6.0);
+
+        // Playoff matches count for half as much.
+        let mut playoff = qual(2, [7, 8, 9], [10, 11, 12], (30, 20));
+        playoff.comp_level = CompLevel::QuarterFinal;
+        elo.add_match(&playoff);
+        assert_eq!(elo.rating(&TeamKey::from_number(7)), MEAN_RATING + 3.0);
+    }
+
+    #[test]
+    fn favorites_gain_less_for_winning() {
+        let mut elo = elo();
+        elo.add_match(&qual(1, [1, 2, 3], [4, 5, 6], (30, 20)));
+        let before = elo.rating(&TeamKey::from_number(1));
+        let expected = elo.win_probability(&qual(2, [1, 2, 3], [4, 5, 6], (-1, -1)), AllianceColor::Red).unwrap();
+        assert!(expected > 0.5);
+        elo.add_match(&qual(2, [1, 2, 3], [4, 5, 6], (30, 20)));
+        assert!((elo.rating(&TeamKey::from_number(1)) - before - 12.0 * (1.0 - expected)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn new_seasons_regress_toward_the_mean() {
+        let mut elo = elo();
+        elo.add_match(&qual(1, [1, 2, 3], [4, 5, 6], (30, 20)));
+        // A tie between new teams in the next season changes nothing but the regression.
+        elo.add_match(&qual_at("2020test", 1, [7, 8, 9], [10, 11, 12], (25, 25), 1_580_000_000));
+        assert!((elo.rating(&TeamKey::from_number(1)) - (MEAN_RATING + 6.0 * 0.8)).abs() < 1e-9);
+        assert_eq!(elo.rating(&TeamKey::from_number(7)), MEAN_RATING);
+    }
+
+    #[test]
+    fn red_outcomes() {
+        assert_eq!(red_outcome(&qual(1, [1, 2, 3], [4, 5, 6], (30, 20))), Some(1.0));
+        assert_eq!(red_outcome(&qual(1, [1, 2, 3], [4, 5, 6], (20, 30))), Some(0.0));
+        assert_eq!(red_outcome(&qual(1, [1, 2, 3], [4, 5, 6], (25, 25))), Some(0.5));
+        assert_eq!(red_outcome(&qual(1, [1, 2, 3], [4, 5, 6], (-1, -1))), None);
+
+        // Without a reported winner the scores decide.
+        let mut m = qual(1, [1, 2, 3], [4, 5, 6], (20, 30));
+        m.winning_alliance = None;
+        assert_eq!(red_outcome(&m), Some(0.0));
+
+        // Unplayed matches change no ratings.
+        let mut elo = elo();
+        elo.add_match(&qual(1, [1, 2, 3], [4, 5, 6], (-1, -1)));
+        assert!(elo.sorted().is_empty());
+    }
+}
@@ -0,0 +1,207 @@
+use std::collections::HashMap;
+use chrono::{DateTime, Utc};
+use futures::Future;
+use the_blue_alliance::event::Event;
+use the_blue_alliance::keys::{EventKey, MatchKey, TeamKey};
+use the_blue_alliance::matches::{AllianceColor, Match};
+use elo;
+use state::{self, Participation};
+
+/// Wins, losses and ties.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Record {
+    pub wins: usize,
+    pub losses: usize,
+    pub ties: usize,
+}
+
+impl Record {
+    /// Counts a match by its outcome: 1 for a win, 0 for a loss and 0.5 for a tie.
+    pub fn add(&mut self, outcome: f64) {
+        if outcome > 0.5 {
+            self.wins += 1;
+        } else if outcome < 0.5 {
+            self.losses += 1;
+        } else {
+            self.ties += 1;
+        }
+    }
+}
+
+/// A match two teams both played in, from the first team's side.
+#[derive(Debug, Clone)]
+pub struct Meeting {
+    pub key: MatchKey,
+    /// Whether the teams were partners rather than opponents.
+    pub together: bool,
+    /// 1 if the first team's alliance won, 0 if it lost and 0.5 for a tie.
+    pub outcome: f64,
+    /// The first team's alliance's score, then the other alliance's.
+    pub scores: (i32, i32),
+}
+
+/// Every match two teams have played with or against each other, in the order played.
+#[derive(Debug, Clone)]
+pub struct Pairing {
+    pub first: TeamKey,
+    pub second: TeamKey,
+    pub meetings: Vec<Meeting>,
+}
+
+impl Pairing {
+    /// The record of matches played together, or against each other from the first team's side.
+    pub fn record(&self, together: bool) -> Record {
+        let mut record = Record::default();
+        for m in self.meetings.iter().filter(|m| m.together == together) {
+            record.add(m.outcome);
+        }
+        record
+    }
+
+    /// The average scores of the first team's alliance and the other alliance in matches played
+    /// together, or against each other, or `None` if there were none.
+    pub fn average_scores(&self, together: bool) -> Option<(f64, f64)> {
+        let meetings: Vec<&Meeting> = self.meetings.iter().filter(|m| m.together == together).collect();
+        if meetings.is_empty() {
+            return None;
+        }
+        let n = meetings.len() as f64;
+        Some((
+            meetings.iter().map(|m| f64::from(m.scores.0)).sum::<f64>() / n,
+            meetings.iter().map(|m| f64::from(m.scores.1)).sum::<f64>() / n,
+        ))
+    }
+}
+
+/// The side `team` played on in `m`, if it counts toward the team's record.
+fn side(m: &Match, team: &TeamKey, participation: Participation) -> Option<AllianceColor> {
+    let color = m.alliance_of(team)?;
+    if participation.is_counted(m.alliances.as_ref()?.get(color), team) {
+        Some(color)
+    } else {
+        None
+    }
+}
+
+/// The matches in `matches` that `first` and `second` both played in, from `first`'s side.
+fn meetings(matches: &[Match], first: &TeamKey, second: &TeamKey, participation: Participation) -> Vec<Meeting> {
+    matches.iter()
+        .filter_map(|m| {
+            let (a, b) = (side(m, first, participation)?, side(m, second, participation)?);
+            let red = elo::red_outcome(m)?;
+            let scores = (m.score_for(a)?, m.score_for(a.opponent())?);
+            Some(Meeting {
+                key: m.key.clone(),
+                together: a == b,
+                outcome: if a == AllianceColor::Red { red } else { 1.0 - red },
+                scores,
+            })
+        })
+        .collect()
+}
+
+/// Finds every match played before `time` between each pair of `teams`, at every event at least
+/// two of them attended in `year`, or in any year if `year` is `None`.
+pub fn history(tba: &the_blue_alliance::TBA, teams: &[TeamKey], year: Option<i32>, time: DateTime<Utc>, participation: Participation) -> Vec<Pairing> {
+    let attended: Vec<Vec<Event>> = futures::future::join_all(teams.iter().map(|t| match year {
+        Some(year) => futures::future::Either::A(Event::for_team_key_in_year(tba, t, year)),
+        None => futures::future::Either::B(Event::for_team_key(tba, t)),
+    })).wait().unwrap();
+
+    let mut attendance: HashMap<EventKey, usize> = HashMap::new();
+    let mut events: Vec<Event> = Vec::new();
+    for e in attended.into_iter().flatten() {
+        let count = attendance.entry(e.key.clone()).or_insert(0);
+        *count += 1;
+        if *count == 2 {
+            events.push(e);
+        }
+    }
+    events.sort_unstable_by_key(|e| e.start_date);
+    debug!("{} shared events", events.len());
+
+    let mut matches: Vec<Match> = futures::future::join_all(events.iter().map(|e| e.matches(tba))).wait().unwrap()
+        .into_iter()
+        .flatten()
+        .filter(|m| state::played_before(m, time))
+        .collect();
+    matches.sort_unstable_by(|a, b| a.cmp_by_time(b));
+
+    let mut pairings = Vec::new();
+    for (i, first) in teams.iter().enumerate() {
+        for second in &teams[i + 1..] {
+            pairings.push(Pairing {
+                first: first.clone(),
+                second: second.clone(),
+                meetings: meetings(&matches, first, second, participation),
+            });
+        }
+    }
+    pairings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use testing::qual;
+
+    fn team(n: u32) -> TeamKey {
+        TeamKey::from_number(n)
+    }
+
+    fn matches() -> Vec<Match> {
+        vec![
+            // Teams 1 and 2 together, winning then tying.
+            qual(1, [1, 2, 3], [4, 5, 6], (60, 40)),
+            qual(2, [4, 5, 6], [1, 2, 3], (50, 50)),
+            // Team 1 against team 2, losing from the blue side.
+            qual(3, [2, 4, 5], [1, 3, 6], (70, 30)),
+            // Not played yet.
+            qual(4, [1, 4, 5], [2, 3, 6], (-1, -1)),
+        ]
+    }
+
+    #[test]
+    fn records_count_ties() {
+        let mut record = Record::default();
+        for &outcome in &[1.0, 0.5, 0.0, 0.5] {
+            record.add(outcome);
+        }
+        assert_eq!((record.wins, record.losses, record.ties), (1, 1, 2));
+    }
+
+    #[test]
+    fn partners_and_opponents_are_kept_apart() {
+        let pairing = Pairing {
+            first: team(1),
+            second: team(2),
+            meetings: meetings(&matches(), &team(1), &team(2), Participation::default()),
+        };
+        assert_eq!(pairing.meetings.len(), 3);
+
+        let together = pairing.record(true);
+        assert_eq!((together.wins, together.losses, together.ties), (1, 0, 1));
+        assert_eq!(pairing.average_scores(true), Some((55.0, 45.0)));
+
+        let against = pairing.record(false);
+        assert_eq!((against.wins, against.losses, against.ties), (0, 1, 0));
+        assert_eq!(pairing.average_scores(false), Some((30.0, 70.0)));
+
+        let strangers = Pairing { first: team(1), second: team(9), meetings: Vec::new() };
+        assert_eq!(strangers.average_scores(true), None);
+    }
+
+    #[test]
+    fn surrogate_appearances_only_count_when_asked() {
+        let mut m = qual(1, [1, 2, 3], [4, 5, 6], (60, 40));
+        m.alliances.as_mut().unwrap().blue.surrogate_team_keys = Some(vec![team(4)]);
+        assert_eq!(side(&m, &team(4), Participation::default()), None);
+        assert_eq!(side(&m, &team(5), Participation::default()), Some(AllianceColor::Blue));
+        let count_surrogates = Participation { count_surrogates: true, ..Participation::default() };
+        assert_eq!(side(&m, &team(4), count_surrogates), Some(AllianceColor::Blue));
+        assert_eq!(side(&m, &team(9), count_surrogates), None);
+
+        assert!(meetings(&[m.clone()], &team(1), &team(4), Participation::default()).is_empty());
+        assert_eq!(meetings(&[m], &team(1), &team(4), count_surrogates).len(), 1);
+    }
+}
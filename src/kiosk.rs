@@ -1,4 +1,3 @@
-use std::collections::HashMap;
 use std::io;
 use tui::Terminal;
 use tui::style::{Style, Color};
@@ -6,6 +5,7 @@ use tui::backend::CrosstermBackend;
 use tui::widgets::{Widget, Block, Borders, Table, Row, Paragraph, Text, Gauge};
 use tui::layout::{Layout, Constraint, Direction, Alignment};
 use futures::Future;
+use the_blue_alliance::keys::EventKey;
 
 fn complvl_to_string(c: the_blue_alliance::matches::CompLevel) -> &'static str {
     match c {
@@ -25,21 +25,50 @@ fn winner_to_string(c: the_blue_alliance::matches::Winner) -> &'static str {
     }
 }
 
-pub fn run(event_key: &str, tba: &the_blue_alliance::TBA) -> Result<(), io::Error>{
+pub fn run(event_key: &EventKey, tba: &the_blue_alliance::TBA, participation: ::state::Participation, strength: Option<f64>) -> Result<(), io::Error>{
     let screen = crossterm::Screen::default();
     let alternate_screen = screen.enable_alternate_modes(true).unwrap();
     let backend = CrosstermBackend::with_alternate_screen(alternate_screen).unwrap();
     let mut terminal = Terminal::new(backend)?;
 
+    let year = event_key.year();
+    let elo_before = ::elo::ratings_at(tba, &[year - 1, year], chrono::Utc::now(), ::elo::EloConfig::default(), participation, Some(event_key));
+
+    // Rankings are kept between restarts, so only matches played since need to be counted.
+    let state_path = format!("kiosk-{}.bin", event_key);
+    let mut state = ::state::EventState::load(&state_path)
+        .filter(|s| s.year == year && s.participation == participation)
+        .unwrap_or_else(|| ::state::EventState::empty(year, participation));
+
     loop {
         let event = the_blue_alliance::event::Event::from_key(tba, event_key).wait().unwrap();
 
-        let matches_f = event.matches(tba);
+        let matches: Vec<_> = event.matches(tba).wait().unwrap();
 
-        let state = ::state::EventState::new(&event, tba, chrono::Utc::now());
+        if state.update(&matches, chrono::Utc::now()) {
+            state.save(&state_path);
+        }
 
-        let matches: Vec<_> = matches_f.wait().unwrap();
+        let mut elo = elo_before.clone();
+        elo.add_matches(matches.clone());
+        let mut elos: Vec<_> = event.teams(tba).wait().unwrap().into_iter()
+            .map(|t| {
+                let rating = elo.rating(&t.key);
+                let change = rating - elo_before.rating(&t.key);
+                (t.key, rating, change)
+            })
+            .collect();
+        elos.sort_unstable_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
         
+        // The last few ranks each team held after its own matches, first place at the top.
+        let teams = state.ranked().count();
+        let trends: std::collections::HashMap<_, _> = state.ranked()
+            .map(|t| {
+                let recent = &t.ranks[t.ranks.len().saturating_sub(TREND_LENGTH)..];
+                (t.team.clone(), ::report::rank_sparkline(recent, teams))
+            })
+            .collect();
+
         let mut schedule: Vec<_> = matches.iter().cloned().filter(|m| m.score_breakdown.is_none()).collect();
         schedule.sort_unstable();
         
@@ -49,40 +78,84 @@ pub fn run(event_key: &str, tba: &the_blue_alliance::TBA) -> Result<(), io::Erro
 
         let (oprs_prog_send, oprs_progress) = std::sync::mpsc::channel::<f32>();
 
+        let year = event.year;
         let handle = std::thread::spawn(move || {
             oprs_prog_send.send(0.0).unwrap();
-            let oprs = ::opr::oprs_from_matches(matches.clone());
-            oprs_prog_send.send(0.45).unwrap();
-            let dprs = ::opr::dprs_from_matches(matches);
-            oprs_prog_send.send(0.95).unwrap();
-            let ccwms = ::opr::ccwms_from_oprs_and_dprs(oprs.clone(), dprs.clone());
+            let design = ::opr::Design::for_matches(&matches, participation, strength)?;
+            let components = ::opr::components(year);
+            let mut columns = Vec::new();
+            for (i, c) in components.iter().enumerate() {
+                oprs_prog_send.send(i as f32 / components.len() as f32).unwrap();
+                columns.push((c.name, design.fit(c.metric)?));
+            }
+            let table = ::opr::ComponentTable::from_columns(columns, true);
+
+            let quals: Vec<_> = matches.iter().filter(|m| m.comp_level == the_blue_alliance::matches::CompLevel::QualificationMatch).cloned().collect();
+            let bonuses = ::opr::Design::for_matches(&quals, participation, strength)
+                .and_then(|design| ::opr::component_table(&design, &::opr::bonuses(year), &::opr::Prior::EventMean))
+                .ok();
+            let schedules = ::schedule::analyze(::season::for_year(year), &matches, &table, bonuses.as_ref(), participation, ::schedule::DEFAULT_RUNS, 0);
             oprs_prog_send.send(1.01).unwrap();
-            (oprs, dprs, ccwms)
+            Ok((table, schedules))
         });
 
         let time_start = std::time::Instant::now();
 
         while let Ok(prog) = oprs_progress.recv() {
             terminal.draw(|f| {
-                render_status(f, &event, &state, schedule.clone(), results.clone(), prog)
+                render_status(f, &event, &state, &trends, schedule.clone(), results.clone(), prog)
             }).unwrap();
             std::thread::yield_now();
         }
 
-        let (oprs, dprs, ccwms) = handle.join().unwrap();
+        let (oprs, schedules) = match handle.join().unwrap() {
+            Ok((oprs, schedules)) => (Ok(oprs), schedules),
+            Err(e) => (Err(e), Vec::new()),
+        };
 
-        std::thread::sleep(std::time::Duration::from_secs(15) - (std::time::Instant::now() - time_start));
+        sleep_until(time_start, 15);
         
         terminal.draw(|f| {
-            render_oprs(f, &event, oprs, dprs, ccwms)
+            render_oprs(f, &event, &oprs, &schedules)
         }).unwrap();
         
-        std::thread::sleep(std::time::Duration::from_secs(30) - (std::time::Instant::now() - time_start));
+        sleep_until(time_start, 30);
+
+        terminal.draw(|f| {
+            render_elo(f, &event, &elos)
+        }).unwrap();
+
+        sleep_until(time_start, 45);
     }
 }
 
-fn render_status<B: tui::backend::Backend>(mut f: tui::Frame<B>, event: &the_blue_alliance::event::Event, state: &::state::EventState, schedule: Vec<the_blue_alliance::matches::Match>, results: Vec<the_blue_alliance::matches::Match>, next_progress: f32) {
+/// How many of a team's most recent ranks the ranking table's sparkline shows.
+const TREND_LENGTH: usize = 12;
+
+/// Sleeps until `secs` seconds after `start`, unless that has already passed.
+fn sleep_until(start: std::time::Instant, secs: u64) {
+    let deadline = start + std::time::Duration::from_secs(secs);
+    let now = std::time::Instant::now();
+    if deadline > now {
+        std::thread::sleep(deadline - now);
+    }
+}
+
+fn delay_to_string(delay: chrono::Duration) -> String {
+    let minutes = delay.num_minutes();
+    if minutes > 0 {
+        format!("Running {} min behind", minutes)
+    } else if minutes < 0 {
+        format!("Running {} min ahead", -minutes)
+    } else {
+        "On schedule".to_owned()
+    }
+}
+
+fn render_status<B: tui::backend::Backend>(mut f: tui::Frame<B>, event: &the_blue_alliance::event::Event, state: &::state::EventState, trends: &std::collections::HashMap<the_blue_alliance::keys::TeamKey, String>, schedule: Vec<the_blue_alliance::matches::Match>, results: Vec<the_blue_alliance::matches::Match>, next_progress: f32) {
     let size = f.size();
+    let tz = event.time_zone();
+    let delay = results.iter().filter_map(|m| m.schedule_delay()).next();
 
     let main_chunks = Layout::default()
         .direction(Direction::Vertical)
@@ -126,30 +199,31 @@ fn render_status<B: tui::backend::Backend>(mut f: tui::Frame<B>, event: &the_blu
         .borders(Borders::ALL);
     results_block.render(&mut f, chunks2[1]);
 
-    if !state.ranking.is_empty() {
+    if state.ranked().next().is_some() {
+    let season = state.season();
+    let widths: Vec<u16> = [4, 12, 6].iter().cloned()
+        .chain(season.tiebreakers().iter().map(|c| std::cmp::max(c.len(), 6) as u16))
+        .chain([6, TREND_LENGTH as u16].iter().cloned())
+        .collect();
     Table::new(
-            ["Rank", "Team", "RS", "Cargo", "Panel", "Climb", "Sandstorm", "Played"].iter(),
-            state.ranking.iter()
-                .map(|t| {
-                    (&t.team,
-                    if let ::state::TeamRankingData::S2019(ref d) = t.ranking {d} else {panic!("Wrong season!")})
-                })
+            ["Rank", "Team", season.ranking_score_name()].iter().chain(season.tiebreakers().iter()).chain(["Played", "Trend"].iter()),
+            state.ranked()
                 .enumerate()
-                .map(|(i, (n, r))| Row::Data(vec![
-                    i.to_string(), 
-                    n.clone(), 
-                    r.ranking_score.to_string(),
-                    r.cargo_points.to_string(),
-                    r.panel_points.to_string(),
-                    r.climb_points.to_string(),
-                    r.sandstorm_points.to_string(),
-                    r.played.to_string()].into_iter()))
+                .map(|(i, t)| Row::Data(vec![
+                    (i + 1).to_string(),
+                    t.team.to_string(),
+                    format!("{:.2}", t.ranking.ranking_score)].into_iter()
+                    .chain(t.ranking.tiebreakers.iter().map(|v| v.to_string()))
+                    .chain(std::iter::once(t.ranking.played.to_string()))
+                    .chain(std::iter::once(trends.get(&t.team).cloned().unwrap_or_default()))
+                    .collect::<Vec<_>>()
+                    .into_iter()))
         )
         .block(ranking_block)
         .header_style(Style::default().fg(Color::Yellow))
         .style(Style::default().fg(Color::White))
         .column_spacing(1)
-        .widths(&[4, 12, 6, 6, 6, 6, 10, 6])
+        .widths(&widths)
         .render(&mut f, chunks[0]);
     } else {
         Paragraph::new([Text::Raw(std::borrow::Cow::Borrowed("NO DATA"))].iter())
@@ -159,20 +233,21 @@ fn render_status<B: tui::backend::Backend>(mut f: tui::Frame<B>, event: &the_blu
     }
 
     Table::new(
-        ["Level", "Set", "Number", "Red1", "Red2", "Red3", "Blue1", "Blue2", "Blue3"].iter(),
+        ["Time", "Level", "Set", "Number", "Red1", "Red2", "Red3", "Blue1", "Blue2", "Blue3"].iter(),
         schedule.into_iter()
             .map(|m| {
                     let na = "N/A".to_owned();
                     Row::Data(vec![
+                        m.start_time(&tz).map(|t| t.format("%a %H:%M").to_string()).unwrap_or_else(|| na.clone()),
                         complvl_to_string(m.comp_level).to_owned(),
                         m.set_number.to_string(),
                         m.match_number.to_string(),
-                        m.alliances.as_ref().map(|a| a.red.team_keys[0].clone()).or_else(|| Some(na.clone())).unwrap(),
-                        m.alliances.as_ref().map(|a| a.red.team_keys[1].clone()).or_else(|| Some(na.clone())).unwrap(),
-                        m.alliances.as_ref().map(|a| a.red.team_keys[2].clone()).or_else(|| Some(na.clone())).unwrap(),
-                        m.alliances.as_ref().map(|a| a.blue.team_keys[0].clone()).or_else(|| Some(na.clone())).unwrap(),
-                        m.alliances.as_ref().map(|a| a.blue.team_keys[1].clone()).or_else(|| Some(na.clone())).unwrap(),
-                        m.alliances.as_ref().map(|a| a.blue.team_keys[2].clone()).or_else(|| Some(na.clone())).unwrap()
+                        m.alliances.as_ref().map(|a| a.red.team_keys[0].to_string()).or_else(|| Some(na.clone())).unwrap(),
+                        m.alliances.as_ref().map(|a| a.red.team_keys[1].to_string()).or_else(|| Some(na.clone())).unwrap(),
+                        m.alliances.as_ref().map(|a| a.red.team_keys[2].to_string()).or_else(|| Some(na.clone())).unwrap(),
+                        m.alliances.as_ref().map(|a| a.blue.team_keys[0].to_string()).or_else(|| Some(na.clone())).unwrap(),
+                        m.alliances.as_ref().map(|a| a.blue.team_keys[1].to_string()).or_else(|| Some(na.clone())).unwrap(),
+                        m.alliances.as_ref().map(|a| a.blue.team_keys[2].to_string()).or_else(|| Some(na.clone())).unwrap()
                     ].into_iter())
                 }
             )
@@ -181,7 +256,7 @@ fn render_status<B: tui::backend::Backend>(mut f: tui::Frame<B>, event: &the_blu
     .header_style(Style::default().fg(Color::Yellow))
     .style(Style::default().fg(Color::White))
     .column_spacing(1)
-    .widths(&[6, 4, 8, 8, 8, 8, 8, 8, 8])
+    .widths(&[9, 6, 4, 8, 8, 8, 8, 8, 8, 8])
     .render(&mut f, chunks2[0]);
 
     Table::new(
@@ -193,12 +268,12 @@ fn render_status<B: tui::backend::Backend>(mut f: tui::Frame<B>, event: &the_blu
                         complvl_to_string(m.comp_level).to_owned(),
                         m.set_number.to_string(),
                         m.match_number.to_string(),
-                        m.alliances.as_ref().map(|a| a.red.team_keys[0].clone()).or_else(|| Some(na.clone())).unwrap(),
-                        m.alliances.as_ref().map(|a| a.red.team_keys[1].clone()).or_else(|| Some(na.clone())).unwrap(),
-                        m.alliances.as_ref().map(|a| a.red.team_keys[2].clone()).or_else(|| Some(na.clone())).unwrap(),
-                        m.alliances.as_ref().map(|a| a.blue.team_keys[0].clone()).or_else(|| Some(na.clone())).unwrap(),
-                        m.alliances.as_ref().map(|a| a.blue.team_keys[1].clone()).or_else(|| Some(na.clone())).unwrap(),
-                        m.alliances.as_ref().map(|a| a.blue.team_keys[2].clone()).or_else(|| Some(na.clone())).unwrap(),
+                        m.alliances.as_ref().map(|a| a.red.team_keys[0].to_string()).or_else(|| Some(na.clone())).unwrap(),
+                        m.alliances.as_ref().map(|a| a.red.team_keys[1].to_string()).or_else(|| Some(na.clone())).unwrap(),
+                        m.alliances.as_ref().map(|a| a.red.team_keys[2].to_string()).or_else(|| Some(na.clone())).unwrap(),
+                        m.alliances.as_ref().map(|a| a.blue.team_keys[0].to_string()).or_else(|| Some(na.clone())).unwrap(),
+                        m.alliances.as_ref().map(|a| a.blue.team_keys[1].to_string()).or_else(|| Some(na.clone())).unwrap(),
+                        m.alliances.as_ref().map(|a| a.blue.team_keys[2].to_string()).or_else(|| Some(na.clone())).unwrap(),
                         winner_to_string(m.winning_alliance.unwrap_or(the_blue_alliance::matches::Winner::None)).to_owned(),
                     ].into_iter())
                 }
@@ -224,13 +299,19 @@ fn render_status<B: tui::backend::Backend>(mut f: tui::Frame<B>, event: &the_blu
             .render(&mut f, toolbar_chunks[0]);
     }
 
-    Paragraph::new([Text::Raw(std::borrow::Cow::Owned(chrono::Local::now().to_rfc2822()))].iter())
+    let clock = chrono::Utc::now().with_timezone(&tz).to_rfc2822();
+    let clock = match delay {
+        Some(delay) => format!("{} | {}", delay_to_string(delay), clock),
+        None => clock,
+    };
+
+    Paragraph::new([Text::Raw(std::borrow::Cow::Owned(clock))].iter())
             .alignment(Alignment::Right)
             .render(&mut f, toolbar_chunks[1]);
 
 }
 
-fn render_oprs<B: tui::backend::Backend>(mut f: tui::Frame<B>, event: &the_blue_alliance::event::Event, mut oprs: HashMap<String, f32>, dprs: HashMap<String, f32>, ccwms: HashMap<String, f32>) {
+fn render_oprs<B: tui::backend::Backend>(mut f: tui::Frame<B>, event: &the_blue_alliance::event::Event, oprs: &Result<::opr::ComponentTable, ::opr::OprError>, schedules: &[::schedule::TeamSchedule]) {
     let size = f.size();
 
     let main_chunks = Layout::default()
@@ -254,7 +335,7 @@ fn render_oprs<B: tui::backend::Backend>(mut f: tui::Frame<B>, event: &the_blue_
         .split(main_chunk);
 
 
-    Paragraph::new([Text::Raw(std::borrow::Cow::Owned(chrono::Local::now().to_rfc2822()))].iter())
+    Paragraph::new([Text::Raw(std::borrow::Cow::Owned(chrono::Utc::now().with_timezone(&event.time_zone()).to_rfc2822()))].iter())
             .alignment(Alignment::Right)
             .render(&mut f, toolbar_chunk);
 
@@ -263,22 +344,29 @@ fn render_oprs<B: tui::backend::Backend>(mut f: tui::Frame<B>, event: &the_blue_
         .borders(Borders::ALL);
     oprs_block.render(&mut f, main_chunk2[0]);
 
-    let mut oprs: Vec<(String, f32)> = oprs.into_iter().collect();
+    let oprs = match oprs {
+        Ok(oprs) => oprs,
+        Err(e) => {
+            Paragraph::new([Text::Raw(std::borrow::Cow::Owned(e.to_string()))].iter())
+                .alignment(Alignment::Center)
+                .block(oprs_block)
+                .render(&mut f, main_chunk2[0]);
+            return;
+        }
+    };
 
-    oprs.sort_unstable_by(|(_, opra), (_, oprb)| opra.partial_cmp(&oprb).unwrap());
-    oprs.reverse();
+    let schedules: std::collections::HashMap<_, _> = schedules.iter().map(|s| (&s.team, s)).collect();
+    let widths: Vec<u16> = std::iter::once(10).chain(oprs.columns.iter().map(|c| std::cmp::max(c.len(), 6) as u16)).chain(std::iter::once(8)).collect();
 
     Table::new(
-        ["Team", "OPR", "DPR", "CCWM"].iter(),
-        oprs.into_iter()
-            .map(|(t, opr)| {
-                    let na = "N/A".to_owned();
-                    Row::Data(vec![
-                        t.clone(),
-                        opr.to_string(),
-                        dprs.get(&t).map(|dpr| dpr.to_string()).unwrap_or_else(|| na.clone()),
-                        ccwms.get(&t).map(|dpr| dpr.to_string()).unwrap_or_else(|| na.clone()),
-                    ].into_iter())
+        ["Team"].iter().chain(oprs.columns.iter()).chain(["Sched RP"].iter()),
+        oprs.sorted_by(0).into_iter()
+            .map(|(t, row)| {
+                    Row::Data(std::iter::once(t.to_string())
+                        .chain(row.iter().map(|e| if e.value.is_nan() { "N/A".to_owned() } else { format!("{:.1}", e.value) }))
+                        .chain(std::iter::once(schedules.get(t).map(|s| format!("{:+.2}", s.luck())).unwrap_or_else(|| "N/A".to_owned())))
+                        .collect::<Vec<_>>()
+                        .into_iter())
                 }
             )
     )
@@ -286,8 +374,58 @@ fn render_oprs<B: tui::backend::Backend>(mut f: tui::Frame<B>, event: &the_blue_
     .header_style(Style::default().fg(Color::Yellow))
     .style(Style::default().fg(Color::White))
     .column_spacing(1)
-    .widths(&[10, 6, 6, 6])
+    .widths(&widths)
     .render(&mut f, main_chunk2[0]);
 
 
-}
\ No newline at end of file
+}
+
+/// Shows each team's Elo rating, and how much it has changed at this event.
+fn render_elo<B: tui::backend::Backend>(mut f: tui::Frame<B>, event: &the_blue_alliance::event::Event, elos: &[(the_blue_alliance::keys::TeamKey, f64, f64)]) {
+    let size = f.size();
+
+    let main_chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(10), Constraint::Length(1)].as_ref())
+        .margin(0)
+        .split(size);
+
+    let main_chunk = main_chunks[0];
+    let toolbar_chunk = main_chunks[1];
+
+    let mut main_block = Block::default()
+        .title(&event.name)
+        .borders(Borders::NONE);
+    main_block.render(&mut f, main_chunk);
+
+    let main_chunk2 = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Min(10)].as_ref())
+        .margin(1)
+        .split(main_chunk);
+
+    Paragraph::new([Text::Raw(std::borrow::Cow::Owned(chrono::Utc::now().with_timezone(&event.time_zone()).to_rfc2822()))].iter())
+            .alignment(Alignment::Right)
+            .render(&mut f, toolbar_chunk);
+
+    let elo_block = Block::default()
+        .title("Elo")
+        .borders(Borders::ALL);
+
+    Table::new(
+        ["Rank", "Team", "Elo", "Change"].iter(),
+        elos.iter().enumerate()
+            .map(|(i, (team, rating, change))| Row::Data(vec![
+                (i + 1).to_string(),
+                team.to_string(),
+                format!("{:.0}", rating),
+                format!("{:+.0}", change),
+            ].into_iter()))
+    )
+    .block(elo_block)
+    .header_style(Style::default().fg(Color::Yellow))
+    .style(Style::default().fg(Color::White))
+    .column_spacing(1)
+    .widths(&[4, 10, 6, 6])
+    .render(&mut f, main_chunk2[0]);
+}
@@ -12,16 +12,25 @@ extern crate itertools;
 extern crate num_traits;
 //extern crate fann;
 extern crate chrono;
+#[macro_use]
 extern crate clap;
 extern crate prettytable;
 extern crate tui;
 extern crate crossterm;
+extern crate rand;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_cbor;
+#[cfg(test)]
+extern crate serde_json;
 
 use std::iter::FromIterator;
 use std::ops::Deref;
-use chrono::TimeZone;
+use chrono::{Datelike, TimeZone};
 use futures::Future;
 use the_blue_alliance::TBA;
+use the_blue_alliance::keys::{EventKey, TeamKey};
 use clap::{
     App, Arg, SubCommand
 };
@@ -31,8 +40,22 @@ use std::collections::HashMap;
 
 
 mod state;
+mod season;
 mod kiosk;
 mod opr;
+mod elo;
+mod predict;
+mod simulate;
+mod backtest;
+mod picklist;
+mod playoffs;
+mod schedule;
+mod consistency;
+mod history;
+mod report;
+mod verify;
+#[cfg(test)]
+mod testing;
 
 fn main() {
     // pretty_env_logger::init();
@@ -59,6 +82,15 @@ fn main() {
             .value_name("TIME")
             .help("Give data for this time in RFC 2822 format (default is now.)")
             .takes_value(true))
+        .arg(Arg::with_name("include-surrogates")
+            .long("include-surrogates")
+            .help("Count matches played as a surrogate toward rankings."))
+        .arg(Arg::with_name("include-dqs")
+            .long("include-dqs")
+            .help("Treat disqualified teams as if they had not been disqualified."))
+        .arg(Arg::with_name("exclude-from-oprs")
+            .long("exclude-from-oprs")
+            .help("Leave surrogate appearances and disqualified teams out of OPRs too, unless included above."))
         .subcommand(SubCommand::with_name("team")
             .about("Does something with a team")
             .arg(Arg::with_name("TEAM")
@@ -78,6 +110,19 @@ fn main() {
                     .takes_value(true))
 
             )
+            .subcommand(SubCommand::with_name("history")
+                .about("Summarizes every season a team has played")
+                .arg(Arg::with_name("skip-elo")
+                    .long("skip-elo")
+                    .help("Leave out Elo ratings, which need every match of every season since the team's first."))
+            )
+            .subcommand(SubCommand::with_name("season")
+                .about("Shows how a team did at each event it attended in a season")
+                .arg(Arg::with_name("csv")
+                    .long("csv")
+                    .help("Print the table as CSV."))
+                .arg(ridge_arg())
+            )
         )
         .subcommand(SubCommand::with_name("event")
             .about("Does something with an event")
@@ -88,6 +133,117 @@ fn main() {
             .subcommand(SubCommand::with_name("info")
                 .about("Displays basic event info")
             )
+            .subcommand(SubCommand::with_name("opr")
+                .about("Displays OPR, DPR, CCWM and component OPRs")
+                .arg(ridge_arg().help("Regularize OPRs toward a prior with this strength. Defaults to only regularizing when there are too few matches."))
+                .arg(Arg::with_name("prior")
+                    .long("prior")
+                    .value_name("PRIOR")
+                    .help("What regularized OPRs start from: the event mean, or each team's OPRs at its previous event.")
+                    .possible_values(&["event", "previous"])
+                    .default_value("event")
+                    .takes_value(true))
+                .arg(Arg::with_name("errors")
+                    .long("errors")
+                    .help("Show the 95% confidence interval of every value."))
+                .arg(Arg::with_name("compare")
+                    .long("compare")
+                    .value_names(&["TEAM1", "TEAM2"])
+                    .help("Test whether two teams' OPRs are statistically distinguishable.")
+                    .takes_value(true))
+            )
+            .subcommand(SubCommand::with_name("verify")
+                .about("Compares our qualification rankings with the official ones")
+                .arg(Arg::with_name("all")
+                    .long("all")
+                    .help("List every team, not just the ones that disagree."))
+            )
+            .subcommand(SubCommand::with_name("trajectory")
+                .about("Shows how each team's rank changed after every qualification match")
+                .arg(Arg::with_name("csv")
+                    .long("csv")
+                    .help("Print every team's rank after every match as CSV."))
+            )
+            .subcommand(SubCommand::with_name("consistency")
+                .about("Shows how much each team's contribution varies from match to match")
+                .arg(Arg::with_name("component")
+                    .long("component")
+                    .value_name("COMPONENT")
+                    .help("The component to measure, e.g. Auto or Climb.")
+                    .default_value("OPR")
+                    .takes_value(true))
+                .arg(ridge_arg())
+            )
+            .subcommand(SubCommand::with_name("predict")
+                .about("Predicts the scores, winners and ranking point bonuses of unplayed matches")
+                .arg(Arg::with_name("model")
+                    .long("model")
+                    .value_name("MODEL")
+                    .help("What to base predictions on.")
+                    .possible_values(&["opr", "components", "elo", "all"])
+                    .default_value("opr")
+                    .takes_value(true))
+                .arg(ridge_arg())
+            )
+            .subcommand(SubCommand::with_name("simulate")
+                .about("Simulates the rest of qualifications to estimate final rankings")
+                .arg(Arg::with_name("runs")
+                    .long("runs")
+                    .value_name("RUNS")
+                    .help("How many times to play out the remaining matches.")
+                    .default_value("10000")
+                    .takes_value(true))
+                .arg(Arg::with_name("seed")
+                    .long("seed")
+                    .value_name("SEED")
+                    .help("Seed for the random number generator. The same seed gives the same results.")
+                    .default_value("0")
+                    .takes_value(true))
+                .arg(ridge_arg())
+            )
+            .subcommand(SubCommand::with_name("picklist")
+                .about("Recommends alliance selection picks for a captain")
+                .arg(Arg::with_name("slot")
+                    .long("slot")
+                    .value_name("SLOT")
+                    .help("The alliance number being picked for.")
+                    .required(true)
+                    .takes_value(true))
+                .arg(Arg::with_name("team")
+                    .long("team")
+                    .value_name("TEAM")
+                    .help("The captain. Defaults to the team ranked at SLOT.")
+                    .takes_value(true))
+                .arg(ridge_arg())
+            )
+            .subcommand(SubCommand::with_name("playoffs")
+                .about("Simulates the playoff bracket to estimate each alliance's chance of winning")
+                .arg(Arg::with_name("alliance")
+                    .long("alliance")
+                    .value_name("TEAMS")
+                    .help("The teams on an alliance, comma separated, captain first. Give all eight in seed order to override TBA's alliances.")
+                    .multiple(true)
+                    .number_of_values(1)
+                    .takes_value(true))
+                .arg(Arg::with_name("format")
+                    .long("format")
+                    .value_name("FORMAT")
+                    .help("The bracket format. Defaults to the event's.")
+                    .possible_values(&["bo3", "double"])
+                    .takes_value(true))
+                .arg(Arg::with_name("runs")
+                    .long("runs")
+                    .value_name("RUNS")
+                    .help("How many times to play out the bracket.")
+                    .default_value("10000")
+                    .takes_value(true))
+                .arg(Arg::with_name("seed")
+                    .long("seed")
+                    .value_name("SEED")
+                    .help("Seed for the random number generator. The same seed gives the same results.")
+                    .default_value("0")
+                    .takes_value(true))
+            )
             .subcommand(SubCommand::with_name("compare")
                 .about("Compares teams across events.")
                 .arg(Arg::with_name("EVENT2")
@@ -96,12 +252,101 @@ fn main() {
                     .index(1))
             )
         )
+        .subcommand(SubCommand::with_name("season")
+            .about("Does something with a whole season")
+            .subcommand(SubCommand::with_name("opr")
+                .about("Ranks teams by OPRs solved from every event they played this season")
+                .arg(Arg::with_name("district")
+                    .long("district")
+                    .value_name("DISTRICT")
+                    .help("Only rank teams in this district, e.g. 2019fim.")
+                    .takes_value(true))
+                .arg(Arg::with_name("division")
+                    .long("division")
+                    .value_name("EVENT")
+                    .help("Only rank teams in this championship division.")
+                    .conflicts_with("district")
+                    .takes_value(true))
+                .arg(Arg::with_name("half-life")
+                    .long("half-life")
+                    .value_name("DAYS")
+                    .help("Weight recent matches more, halving a match's weight every DAYS days.")
+                    .takes_value(true))
+                .arg(ridge_arg().help("Regularize OPRs toward the season mean with this strength. Defaults to only regularizing when there are too few matches."))
+                .arg(Arg::with_name("errors")
+                    .long("errors")
+                    .help("Show the 95% confidence interval of every value."))
+            )
+        )
+        .subcommand(SubCommand::with_name("elo")
+            .about("Ranks teams by Elo rating")
+            .arg(Arg::with_name("seasons")
+                .long("seasons")
+                .value_name("SEASONS")
+                .help("How many seasons of matches to rate, ending with the current one.")
+                .default_value("2")
+                .takes_value(true))
+            .arg(Arg::with_name("k")
+                .long("k")
+                .value_name("K")
+                .help("How far one qualification match can move a rating.")
+                .takes_value(true))
+            .arg(Arg::with_name("playoff-weight")
+                .long("playoff-weight")
+                .value_name("WEIGHT")
+                .help("Multiplier on K for playoff matches.")
+                .takes_value(true))
+            .arg(Arg::with_name("regression")
+                .long("regression")
+                .value_name("FRACTION")
+                .help("How far ratings are pulled back to the mean between seasons.")
+                .takes_value(true))
+            .arg(Arg::with_name("event")
+                .short("e")
+                .long("event")
+                .value_name("EVENT")
+                .help("Only show teams at this event.")
+                .takes_value(true))
+        )
+        .subcommand(SubCommand::with_name("backtest")
+            .about("Measures how well each prediction model would have predicted past matches")
+            .arg(Arg::with_name("event")
+                .short("e")
+                .long("event")
+                .value_name("EVENT")
+                .help("Replay only this event. Defaults to every event in the season that has finished.")
+                .takes_value(true))
+            .arg(Arg::with_name("model")
+                .long("model")
+                .value_name("MODEL")
+                .help("Which model to test.")
+                .possible_values(&["opr", "components", "elo", "all"])
+                .default_value("all")
+                .takes_value(true))
+            .arg(ridge_arg())
+        )
+        .subcommand(SubCommand::with_name("history")
+            .about("Shows the record of teams playing with and against each other")
+            .arg(Arg::with_name("TEAMS")
+                .help("The teams to compare. Every pair of them is shown.")
+                .required(true)
+                .multiple(true)
+                .min_values(2)
+                .index(1))
+            .arg(Arg::with_name("all-years")
+                .long("all-years")
+                .help("Look at every season the teams have played, not just the one given by --year."))
+            .arg(Arg::with_name("matches")
+                .long("matches")
+                .help("List every match each pair played in, as partners or opponents."))
+        )
         .subcommand(SubCommand::with_name("kiosk")
             .about("Displays event info prettily")
             .arg(Arg::with_name("EVENT")
                 .help("The event to display.")
                 .required(true)
                 .index(1))
+            .arg(ridge_arg())
         )
         .get_matches();
 
@@ -111,13 +356,19 @@ fn main() {
         chrono::Utc::now()
     };
 
+    let participation = state::Participation {
+        count_surrogates: matches.is_present("include-surrogates"),
+        ignore_disqualifications: matches.is_present("include-dqs"),
+        exclude_from_oprs: matches.is_present("exclude-from-oprs"),
+    };
+
     match matches.subcommand() {
         ("team", Some(sub_matches)) => {
-            let team = sub_matches.value_of("TEAM").expect("No team specified.");
+            let team = value_t!(sub_matches, "TEAM", TeamKey).unwrap_or_else(|e| e.exit());
 
             match sub_matches.subcommand() {
                 ("info", Some(sub_matches)) => {
-                    let info = the_blue_alliance::team::Team::from_key(&tba, team).wait().unwrap();
+                    let info = the_blue_alliance::team::Team::from_key(&tba, &team).wait().unwrap();
                     println!("Team #{}", info.team_number);
                     println!("Name: {}", info.nickname.clone().unwrap_or_else(|| "N/A".to_owned()));
                     println!("Motto: {}", info.motto.clone().unwrap_or_else(|| "N/A".to_owned()));
@@ -125,9 +376,62 @@ fn main() {
                     println!("Rookie year: {}", info.rookie_year);
                     println!("Website: {}", info.website.clone().unwrap_or_else(|| "N/A".to_owned()));
                 },
+                ("history", Some(sub_matches)) => {
+                    let info = the_blue_alliance::team::Team::from_key(&tba, &team).wait().unwrap();
+                    let history = report::team_history(&tba, &info, time, participation, !sub_matches.is_present("skip-elo"));
+
+                    let mut table = prettytable::Table::new();
+                    table.set_titles(prettytable::Row::new(["Year", "Events", "Best Finish", "Rank %ile", "Elo", "Awards"].iter().map(|c| prettytable::Cell::new(c)).collect()));
+                    for y in &history {
+                        table.add_row(prettytable::Row::new([
+                            y.year.to_string(),
+                            y.events.to_string(),
+                            y.best_finish.map(|f| f.to_string()).unwrap_or_else(|| "N/A".to_owned()),
+                            y.rank_percentile.map(|p| format!("{:.0}", p)).unwrap_or_else(|| "N/A".to_owned()),
+                            y.elo.map(|r| format!("{:.0}", r)).unwrap_or_else(|| "N/A".to_owned()),
+                            y.awards.join("; "),
+                        ].iter().map(|c| prettytable::Cell::new(c)).collect()));
+                    }
+                    println!("{} since {}:", info.nickname.clone().unwrap_or_else(|| team.to_string()), info.rookie_year);
+                    table.printstd();
+                    println!("Rank %ile is the average share of teams ranked below the team at its events.");
+                },
+                ("season", Some(sub_matches)) => {
+                    let year = selected_year(&matches, time);
+                    let strength = ridge_strength(sub_matches);
+                    let info = the_blue_alliance::team::Team::from_key(&tba, &team).wait().unwrap();
+                    let report = report::season_report(&tba, &info, year, time, participation, strength);
+
+                    let mut table = prettytable::Table::new();
+                    table.set_titles(prettytable::Row::new(["Event", "Start", "Rank", "Record", "OPR", "CCWM", "Playoffs", "Awards"].iter().map(|c| prettytable::Cell::new(c)).collect()));
+                    for e in &report.events {
+                        table.add_row(prettytable::Row::new([
+                            e.event.name.clone(),
+                            e.event.start_date.to_string(),
+                            e.rank.map(|r| r.to_string()).unwrap_or_else(|| "N/A".to_owned()),
+                            format!("{}-{}-{}", e.record.wins, e.record.losses, e.record.ties),
+                            e.opr.map(|v| format!("{:.1}", v)).unwrap_or_else(|| "N/A".to_owned()),
+                            e.ccwm.map(|v| format!("{:.1}", v)).unwrap_or_else(|| "N/A".to_owned()),
+                            e.playoffs.map(|p| p.to_string()).unwrap_or_else(|| "N/A".to_owned()),
+                            e.awards.join("; "),
+                        ].iter().map(|c| prettytable::Cell::new(c)).collect()));
+                    }
+
+                    if sub_matches.is_present("csv") {
+                        table.to_csv(std::io::stdout()).unwrap();
+                        return;
+                    }
+                    println!("{} in {}:", info.nickname.clone().unwrap_or_else(|| team.to_string()), year);
+                    table.printstd();
+                    match report.opr_trend() {
+                        Some(trend) => println!("OPR trend: {:+.1} per event {}", trend, report::sparkline(&report.oprs())),
+                        None => println!("OPR trend: N/A"),
+                    }
+                },
                 ("status", Some(sub_matches)) => {
-                    let infos = the_blue_alliance::event::Event::for_team_key(&tba, team).wait().unwrap();
-                    let info = if let Some(event) = sub_matches.value_of("event") {
+                    let infos = the_blue_alliance::event::Event::for_team_key(&tba, &team).wait().unwrap();
+                    let info = if sub_matches.is_present("event") {
+                        let event = value_t!(sub_matches, "event", EventKey).unwrap_or_else(|e| e.exit());
                         infos.into_iter().find(|e| e.key == event).expect("Cannot find event")
                     } else {
                         infos.into_iter().filter(|e| chrono::Utc.from_utc_date(&e.start_date) < chrono::Utc::today()).max_by_key(|e| e.start_date).expect("No events found for team")
@@ -135,20 +439,17 @@ fn main() {
 
                     println!("Event Name: {}", info.name);
 
-                    let event_info = state::EventState::new(&info, &tba, time.clone());
+                    let event_info = state::EventState::new(&info, &tba, time.clone(), participation);
 
                     println!("Team rank: {}", event_info.rank_of_team(&team).unwrap());
 
                     let team_data = event_info.team_data(&team).unwrap();
 
-                    if let state::TeamRankingData::S2019(dat) = team_data {
-                        println!("Panking points: {}", dat.ranking_points);
-                        println!("Matches played: {}", dat.played);
-                        println!("Ranking Score: {}", dat.ranking_score);
-                        println!("Cargo Points: {}", dat.cargo_points);
-                        println!("Panel Points: {}", dat.panel_points);
-                        println!("Climb Points: {}", dat.climb_points);
-                        println!("Sandstorm Points: {}", dat.sandstorm_points);
+                    println!("Ranking points: {}", team_data.ranking_points);
+                    println!("Matches played: {}", team_data.played);
+                    println!("{}: {:.2}", event_info.season().ranking_score_name(), team_data.ranking_score);
+                    for (name, value) in event_info.season().tiebreakers().iter().zip(team_data.tiebreakers.iter()) {
+                        println!("{}: {}", name, value);
                     }
 
                 },
@@ -158,10 +459,10 @@ fn main() {
 
         },
         ("event", Some(sub_matches)) => {
-            let event = sub_matches.value_of("EVENT").expect("No event specified.");
+            let event = value_t!(sub_matches, "EVENT", EventKey).unwrap_or_else(|e| e.exit());
             match sub_matches.subcommand() {
                 ("info", Some(_)) => {
-                    let info = the_blue_alliance::event::Event::from_key(&tba, event).wait().unwrap();
+                    let info = the_blue_alliance::event::Event::from_key(&tba, &event).wait().unwrap();
 
                     println!("Event Name: {}", info.name);
                     println!("District: {}", info.district.as_ref().map(|d| d.display_name.deref()).unwrap_or("N/A"));
@@ -170,15 +471,441 @@ fn main() {
                     println!("Teams:");
                     let mut teams = info.teams(&tba).wait().unwrap();
                     teams.sort_unstable_by_key(|t| t.team_number);
-                    for t in  teams {
-                        println!("- {}: {}", t.team_number, t.nickname.as_ref().map(|n| n.deref()).unwrap_or("N/A"));
+
+                    let event_matches = info.matches(&tba).wait().unwrap();
+                    let played: Vec<_> = event_matches.iter().filter(|m| state::played_before(m, time)).cloned().collect();
+                    let predictor = predict::Predictor::new(info.year, &played, participation, None, None);
+                    let schedules: HashMap<TeamKey, schedule::TeamSchedule> = match predictor.oprs() {
+                        Some(oprs) => schedule::analyze(season::for_year(info.year), &event_matches, oprs, predictor.bonus_oprs(), participation, schedule::DEFAULT_RUNS, 0)
+                            .into_iter()
+                            .map(|s| (s.team.clone(), s))
+                            .collect(),
+                        None => HashMap::new(),
+                    };
+
+                    let mut table = prettytable::Table::new();
+                    table.set_titles(prettytable::Row::new(["Team", "Name", "Partners", "Opponents", "Schedule RP"].iter().map(|c| prettytable::Cell::new(c)).collect()));
+                    for t in teams {
+                        let sos = schedules.get(&t.key);
+                        table.add_row(prettytable::Row::new([
+                            t.team_number.to_string(),
+                            t.nickname.clone().unwrap_or_else(|| "N/A".to_owned()),
+                            sos.map(|s| format!("{:.1}", s.partner_strength)).unwrap_or_else(|| "N/A".to_owned()),
+                            sos.map(|s| format!("{:.1}", s.opponent_strength)).unwrap_or_else(|| "N/A".to_owned()),
+                            sos.map(|s| format!("{:+.2}", s.luck())).unwrap_or_else(|| "N/A".to_owned()),
+                        ].iter().map(|c| prettytable::Cell::new(c)).collect()));
+                    }
+                    table.printstd();
+                    if !schedules.is_empty() {
+                        println!("Partners and Opponents are the average OPR sums a team plays with and against in qualifications.");
+                        println!("Schedule RP is the ranking points the schedule is expected to gain or cost a team compared to a random one.");
+                    }
+                },
+                ("opr", Some(sub_matches)) => {
+                    let info = the_blue_alliance::event::Event::from_key(&tba, &event).wait().unwrap();
+                    let mut event_matches = info.matches(&tba).wait().unwrap();
+                    event_matches.retain(|m| state::played_before(m, time));
+
+                    let use_previous = sub_matches.value_of("prior") == Some("previous");
+                    let strength = ridge_strength(sub_matches).or(if use_previous { Some(opr::DEFAULT_STRENGTH) } else { None });
+                    let previous = if use_previous {
+                        Some(opr::previous_event_table(&tba, &info, participation, opr::DEFAULT_STRENGTH))
+                    } else {
+                        None
+                    };
+                    let prior = match previous {
+                        Some(ref table) => opr::Prior::Table(table),
+                        None => opr::Prior::EventMean,
+                    };
+
+                    let components = opr::components(info.year);
+                    let design = match opr::Design::for_matches(&event_matches, participation, strength) {
+                        Ok(design) => design,
+                        Err(e) => {
+                            println!("Cannot compute OPRs: {}", e);
+                            return;
+                        }
+                    };
+
+                    if sub_matches.is_present("compare") {
+                        let teams = values_t!(sub_matches, "compare", TeamKey).unwrap_or_else(|e| e.exit());
+                        let fit = match design.fit(components[0].metric) {
+                            Ok(fit) => fit,
+                            Err(e) => {
+                                println!("Cannot compute OPRs: {}", e);
+                                return;
+                            }
+                        };
+                        if let Some(team) = teams.iter().find(|t| !design.teams().contains(t)) {
+                            invalid_value(&format!("Team {} did not play at this event", team));
+                        }
+                        let difference = fit.difference(&teams[0], &teams[1]).unwrap_or_else(|| invalid_value("Teams did not play at this event"));
+                        let (low, high) = difference.confidence_interval();
+                        println!("OPR difference {} - {}: {:.1} (95% CI {:.1} to {:.1})", teams[0], teams[1], difference.value, low, high);
+                        if fit.distinguishable(&teams[0], &teams[1]) == Some(true) {
+                            println!("The difference is statistically significant.");
+                        } else {
+                            println!("The teams' OPRs are not statistically distinguishable.");
+                        }
+                        return;
+                    }
+
+                    let oprs = match opr::component_table(&design, &components, &prior) {
+                        Ok(oprs) => oprs,
+                        Err(e) => {
+                            println!("Cannot compute OPRs: {}", e);
+                            return;
+                        }
+                    };
+
+                    print_component_table(&oprs, sub_matches.is_present("errors"));
+                },
+                ("verify", Some(sub_matches)) => {
+                    let info = the_blue_alliance::event::Event::from_key(&tba, &event).wait().unwrap();
+                    let official = match info.rankings(&tba).wait().unwrap() {
+                        Some(official) => official,
+                        None => {
+                            println!("No official rankings yet");
+                            return;
+                        }
+                    };
+                    let state = state::EventState::new(&info, &tba, time, participation);
+                    let comparisons = verify::compare(&official, &state);
+
+                    let mut table = prettytable::Table::new();
+                    table.set_titles(prettytable::Row::new(["Team", "Official", "Computed", "Differences (official / computed)"].iter().map(|c| prettytable::Cell::new(c)).collect()));
+                    for c in comparisons.iter().filter(|c| sub_matches.is_present("all") || !c.agrees()) {
+                        let rank = |r: Option<usize>| r.map(|r| r.to_string()).unwrap_or_else(|| "N/A".to_owned());
+                        let differences: Vec<String> = c.differences.iter()
+                            .map(|d| format!("{} {:.*} / {:.*}", d.name, d.precision, d.official, d.precision, d.computed))
+                            .collect();
+                        table.add_row(prettytable::Row::new([
+                            c.team.to_string(),
+                            rank(c.official_rank),
+                            rank(c.computed_rank),
+                            differences.join(", "),
+                        ].iter().map(|c| prettytable::Cell::new(c)).collect()));
+                    }
+                    table.printstd();
+
+                    let agreeing = comparisons.iter().filter(|c| c.agrees()).count();
+                    println!("{} of {} teams agree", agreeing, comparisons.len());
+                    let season = state.season();
+                    if season.checked_sort_orders() == 0 {
+                        println!("We have no ranking rules for {}, so only ranks, matches played and DQs were compared.", info.year);
+                    } else if season.checked_sort_orders() <= season.tiebreakers().len() {
+                        println!("Only the first {} sort orders follow the {} rules; the rest were not compared.", season.checked_sort_orders(), info.year);
+                    }
+                    if comparisons.iter().any(|c| c.differences.iter().any(|d| d.name == "Played")) {
+                        println!("Differences in matches played usually come from surrogate appearances; see --include-surrogates.");
+                    }
+                    if comparisons.iter().any(|c| c.differences.iter().any(|d| d.name == "DQs")) {
+                        println!("Differences in DQs usually mean the match data is missing disqualifications.");
+                    }
+                },
+                ("trajectory", Some(sub_matches)) => {
+                    let info = the_blue_alliance::event::Event::from_key(&tba, &event).wait().unwrap();
+                    let event_matches = info.matches(&tba).wait().unwrap();
+                    let trajectory = state::trajectory(info.year, &event_matches, time, participation);
+
+                    let mut teams: Vec<(&TeamKey, &state::TeamTrajectory)> = trajectory.teams.iter().collect();
+                    teams.sort_by_key(|(t, tr)| (tr.ranks.last().cloned().unwrap_or(None), *t));
+
+                    if sub_matches.is_present("csv") {
+                        let mut table = prettytable::Table::new();
+                        table.set_titles(prettytable::Row::new(std::iter::once("Team".to_owned()).chain(trajectory.matches.iter().map(|m| m.to_string())).map(|c| prettytable::Cell::new(&c)).collect()));
+                        for (team, tr) in &teams {
+                            table.add_row(prettytable::Row::new(std::iter::once(team.to_string())
+                                .chain(tr.ranks.iter().map(|r| r.map(|r| r.to_string()).unwrap_or_default()))
+                                .map(|c| prettytable::Cell::new(&c))
+                                .collect()));
+                        }
+                        table.to_csv(std::io::stdout()).unwrap();
+                        return;
+                    }
+
+                    let mut table = prettytable::Table::new();
+                    table.set_titles(prettytable::Row::new(["Rank", "Team", "Best", "Worst", "Trajectory"].iter().map(|c| prettytable::Cell::new(c)).collect()));
+                    for (team, tr) in &teams {
+                        let ranks = &tr.after_own_matches;
+                        table.add_row(prettytable::Row::new([
+                            tr.ranks.last().cloned().unwrap_or(None).map(|r| r.to_string()).unwrap_or_else(|| "N/A".to_owned()),
+                            team.to_string(),
+                            ranks.iter().min().map(|r| r.to_string()).unwrap_or_else(|| "N/A".to_owned()),
+                            ranks.iter().max().map(|r| r.to_string()).unwrap_or_else(|| "N/A".to_owned()),
+                            report::rank_sparkline(ranks, teams.len()),
+                        ].iter().map(|c| prettytable::Cell::new(c)).collect()));
+                    }
+                    table.printstd();
+                    println!("Best, worst and trajectory are the team's rank after each of its own {} matches played.", trajectory.matches.len());
+                },
+                ("consistency", Some(sub_matches)) => {
+                    let info = the_blue_alliance::event::Event::from_key(&tba, &event).wait().unwrap();
+                    let mut event_matches = info.matches(&tba).wait().unwrap();
+                    event_matches.retain(|m| state::played_before(m, time));
+                    let strength = ridge_strength(sub_matches);
+
+                    let components = opr::components(info.year);
+                    let name = sub_matches.value_of("component").unwrap();
+                    let column = match components.iter().position(|c| c.name.eq_ignore_ascii_case(name)) {
+                        Some(column) => column,
+                        None => {
+                            println!("{} has no {} component. Choose one of: {}", info.year, name, components.iter().map(|c| c.name).collect::<Vec<_>>().join(", "));
+                            return;
+                        }
+                    };
+                    let oprs = match opr::Design::for_matches(&event_matches, participation, strength)
+                        .and_then(|design| opr::component_table(&design, &components, &opr::Prior::EventMean)) {
+                        Ok(oprs) => oprs,
+                        Err(e) => {
+                            println!("Cannot compute OPRs: {}", e);
+                            return;
+                        }
+                    };
+
+                    let mut table = prettytable::Table::new();
+                    table.set_titles(prettytable::Row::new(["Rank", "Team", "Matches", "Mean", "SD", "Min", "25%", "Median", "75%", "Max", "Trend"].iter().map(|c| prettytable::Cell::new(c)).collect()));
+                    for (rank, c) in consistency::contributions(&event_matches, &oprs, &components[column], column, participation).iter().enumerate() {
+                        table.add_row(prettytable::Row::new([
+                            (rank + 1).to_string(),
+                            c.team.to_string(),
+                            c.values.len().to_string(),
+                            format!("{:.1}", c.mean()),
+                            c.standard_deviation().map(|sd| format!("{:.1}", sd)).unwrap_or_else(|| "N/A".to_owned()),
+                            format!("{:.1}", c.min()),
+                            format!("{:.1}", c.percentile(0.25)),
+                            format!("{:.1}", c.percentile(0.5)),
+                            format!("{:.1}", c.percentile(0.75)),
+                            format!("{:.1}", c.max()),
+                            c.trend().map(|t| format!("{:+.2}", t)).unwrap_or_else(|| "N/A".to_owned()),
+                        ].iter().map(|c| prettytable::Cell::new(c)).collect()));
+                    }
+                    table.printstd();
+                    println!("A team's contribution to a match is its alliance's {} less its partners' OPRs. Trend is the change per match.", components[column].name);
+                },
+                ("predict", Some(sub_matches)) => {
+                    let info = the_blue_alliance::event::Event::from_key(&tba, &event).wait().unwrap();
+                    let (played, mut upcoming): (Vec<_>, Vec<_>) = info.matches(&tba).wait().unwrap()
+                        .into_iter()
+                        .partition(|m| m.score_breakdown.is_some() && state::played_before(m, time));
+                    upcoming.sort_unstable();
+
+                    let models = if sub_matches.value_of("model") == Some("all") {
+                        predict::Model::ALL.to_vec()
+                    } else {
+                        vec![value_t!(sub_matches, "model", predict::Model).unwrap_or_else(|e| e.exit())]
+                    };
+                    let strength = ridge_strength(sub_matches);
+                    let elo = if models.contains(&predict::Model::Elo) {
+                        let mut elo = elo::ratings_at(&tba, &[info.year - 1, info.year], time, elo::EloConfig::default(), participation, Some(&info.key));
+                        elo.add_matches(played.clone());
+                        Some(elo)
+                    } else {
+                        None
+                    };
+                    let predictor = predict::Predictor::new(info.year, &played, participation, strength, elo);
+
+                    let bonuses: Vec<&str> = opr::bonuses(info.year).iter().map(|b| b.name).collect();
+                    let mut table = prettytable::Table::new();
+                    table.set_titles(prettytable::Row::new(["Match", "Model", "Red", "Blue", "Red Score", "Blue Score", "Red Win"].iter()
+                        .chain(bonuses.iter())
+                        .map(|c| prettytable::Cell::new(c))
+                        .collect()));
+                    for m in &upcoming {
+                        let alliances = match m.alliances {
+                            Some(ref alliances) => alliances,
+                            None => continue,
+                        };
+                        for &model in &models {
+                            let prediction = predictor.predict(m, model);
+                            let score = |color| prediction.as_ref().and_then(|p| p.score(color)).map(|s| format!("{:.0}", s)).unwrap_or_else(|| "N/A".to_owned());
+                            let mut row = vec![
+                                m.key.to_string(),
+                                model.to_string(),
+                                alliances.red.team_keys.iter().map(|t| t.number().to_string()).collect::<Vec<_>>().join(" "),
+                                alliances.blue.team_keys.iter().map(|t| t.number().to_string()).collect::<Vec<_>>().join(" "),
+                                score(the_blue_alliance::matches::AllianceColor::Red),
+                                score(the_blue_alliance::matches::AllianceColor::Blue),
+                                prediction.as_ref().map(|p| format!("{:.0}%", p.red_win * 100.0)).unwrap_or_else(|| "N/A".to_owned()),
+                            ];
+                            for name in &bonuses {
+                                row.push(prediction.as_ref()
+                                    .and_then(|p| p.bonuses.iter().find(|b| b.name == *name))
+                                    .map(|b| format!("{:.0}% / {:.0}%", b.red * 100.0, b.blue * 100.0))
+                                    .unwrap_or_else(|| "N/A".to_owned()));
+                            }
+                            table.add_row(prettytable::Row::new(row.iter().map(|c| prettytable::Cell::new(c)).collect()));
+                        }
+                    }
+                    table.printstd();
+                },
+                ("simulate", Some(sub_matches)) => {
+                    let info = the_blue_alliance::event::Event::from_key(&tba, &event).wait().unwrap();
+                    let runs = value_t!(sub_matches, "runs", usize).unwrap_or_else(|e| e.exit());
+                    let seed = value_t!(sub_matches, "seed", u64).unwrap_or_else(|e| e.exit());
+                    let strength = ridge_strength(sub_matches);
+
+                    let (played, remaining): (Vec<_>, Vec<_>) = info.matches(&tba).wait().unwrap()
+                        .into_iter()
+                        .filter(|m| m.comp_level == the_blue_alliance::matches::CompLevel::QualificationMatch)
+                        .partition(|m| m.score_for(the_blue_alliance::matches::AllianceColor::Red).is_some() && state::played_before(m, time));
+                    let state = state::EventState::new(&info, &tba, time, participation);
+                    let design = match opr::Design::for_matches(&played, participation, strength) {
+                        Ok(design) => design,
+                        Err(e) => {
+                            println!("Cannot compute OPRs: {}", e);
+                            return;
+                        }
+                    };
+
+                    let outlooks = match simulate::simulate(&pool, &state, &remaining, &design, participation, runs, seed) {
+                        Ok(outlooks) => outlooks,
+                        Err(e) => {
+                            println!("Cannot simulate rankings: {}", e);
+                            return;
+                        }
+                    };
+
+                    println!("{} matches left, {} runs", remaining.len(), runs);
+                    let mut table = prettytable::Table::new();
+                    table.set_titles(prettytable::Row::new(["Team", "Now", "Mean Rank", "Rank 90%", "Exp. RP", "Top 8", "First"].iter().map(|c| prettytable::Cell::new(c)).collect()));
+                    for o in outlooks {
+                        table.add_row(prettytable::Row::new([
+                            o.team.to_string(),
+                            state.rank_of_team(&o.team).map(|r| r.to_string()).unwrap_or_else(|| "N/A".to_owned()),
+                            format!("{:.1}", o.mean_rank()),
+                            format!("{}-{}", o.percentile(0.05), o.percentile(0.95)),
+                            format!("{:.1}", o.expected_ranking_points),
+                            format!("{:.1}%", o.probability_of_top(8) * 100.0),
+                            format!("{:.1}%", o.probability_of_top(1) * 100.0),
+                        ].iter().map(|c| prettytable::Cell::new(c)).collect()));
                     }
+                    table.printstd();
+                },
+                ("picklist", Some(sub_matches)) => {
+                    let info = the_blue_alliance::event::Event::from_key(&tba, &event).wait().unwrap();
+                    let slot = value_t!(sub_matches, "slot", usize).unwrap_or_else(|e| e.exit());
+                    let strength = ridge_strength(sub_matches);
+
+                    let mut event_matches = info.matches(&tba).wait().unwrap();
+                    event_matches.retain(|m| state::played_before(m, time));
+                    let components = opr::components(info.year);
+                    let oprs = match opr::Design::for_matches(&event_matches, participation, strength)
+                        .and_then(|design| opr::component_table(&design, &components, &opr::Prior::EventMean)) {
+                        Ok(oprs) => oprs,
+                        Err(e) => {
+                            println!("Cannot compute OPRs: {}", e);
+                            return;
+                        }
+                    };
+                    let contributions = consistency::contributions(&event_matches, &oprs, &components[0], 0, participation);
+
+                    let state = state::EventState::new(&info, &tba, time, participation);
+                    let mut ranking: Vec<TeamKey> = state.ranked().map(|t| t.team.clone()).collect();
+                    let mut unranked: Vec<TeamKey> = info.teams(&tba).wait().unwrap().into_iter()
+                        .map(|t| t.key)
+                        .filter(|t| !ranking.contains(t))
+                        .collect();
+                    unranked.sort_unstable();
+                    ranking.extend(unranked);
+
+                    let alliances = picklist::alliances_count(ranking.len());
+                    if slot == 0 || slot > alliances {
+                        invalid_value(&format!("SLOT must be between 1 and {}, the number of alliances at this event", alliances));
+                    }
+                    let captain = if sub_matches.is_present("team") {
+                        value_t!(sub_matches, "team", TeamKey).unwrap_or_else(|e| e.exit())
+                    } else {
+                        ranking[slot - 1].clone()
+                    };
+
+                    let list = picklist::picklist(&oprs, &contributions, &ranking, &captain, slot).unwrap_or_else(|e| invalid_value(&e.to_string()));
+
+                    println!("Picks for alliance {}, captained by {}:", slot, captain);
+                    for (name, pick, taken) in [("First", &list.first, &list.taken_before_first), ("Second", &list.second, &list.taken_before_second)].iter() {
+                        if let Some(pick) = pick {
+                            println!("{} pick: {} ({})", name, pick.team, pick.reasons.join(", "));
+                            if !taken.is_empty() {
+                                println!("  Expected to be gone: {}", taken.iter().map(|(seed, t)| format!("{} (alliance {})", t, seed)).collect::<Vec<_>>().join(", "));
+                            }
+                        }
+                    }
+
+                    let mut table = prettytable::Table::new();
+                    table.set_titles(prettytable::Row::new(["Rank", "Team", "Value", "Why"].iter().map(|c| prettytable::Cell::new(c)).collect()));
+                    for (i, pick) in list.ranked.iter().enumerate() {
+                        table.add_row(prettytable::Row::new([
+                            (i + 1).to_string(),
+                            pick.team.to_string(),
+                            format!("{:.1}", pick.value),
+                            pick.reasons.join(", "),
+                        ].iter().map(|c| prettytable::Cell::new(c)).collect()));
+                    }
+                    table.printstd();
+                },
+                ("playoffs", Some(sub_matches)) => {
+                    let info = the_blue_alliance::event::Event::from_key(&tba, &event).wait().unwrap();
+                    let runs = value_t!(sub_matches, "runs", usize).unwrap_or_else(|e| e.exit());
+                    let seed = value_t!(sub_matches, "seed", u64).unwrap_or_else(|e| e.exit());
+                    let format = match sub_matches.value_of("format") {
+                        Some("bo3") => playoffs::Format::BestOfThree,
+                        Some("double") => playoffs::Format::DoubleElimination,
+                        _ => match playoffs::Format::for_event(&info) {
+                            Ok(format) => format,
+                            Err(e) => {
+                                println!("{}. Choose a bracket with --format.", e);
+                                return;
+                            }
+                        },
+                    };
+
+                    let alliances: Vec<Vec<TeamKey>> = if let Some(alliances) = sub_matches.values_of("alliance") {
+                        alliances.map(|a| a.split(',').map(|t| t.parse().unwrap_or_else(|e: the_blue_alliance::keys::ParseKeyError| invalid_value(&e.to_string()))).collect()).collect()
+                    } else {
+                        match info.alliances(&tba).wait().unwrap() {
+                            Some(alliances) => alliances.iter().map(|a| a.teams().into_iter().cloned().collect()).collect(),
+                            None => {
+                                println!("Alliances have not been selected yet. Enter them with --alliance.");
+                                return;
+                            }
+                        }
+                    };
+                    let mut event_matches = info.matches(&tba).wait().unwrap();
+                    event_matches.retain(|m| state::played_before(m, time));
+                    let oprs = match opr::Design::for_matches(&event_matches, participation, None)
+                        .and_then(|design| opr::component_table(&design, &opr::components(info.year), &opr::Prior::EventMean)) {
+                        Ok(oprs) => oprs,
+                        Err(e) => {
+                            println!("Cannot compute OPRs: {}", e);
+                            return;
+                        }
+                    };
+
+                    let outlooks = match playoffs::simulate(format, &alliances, &oprs, &event_matches, runs, seed) {
+                        Ok(outlooks) => outlooks,
+                        Err(e) => {
+                            println!("Cannot simulate playoffs: {}", e);
+                            return;
+                        }
+                    };
+                    let mut table = prettytable::Table::new();
+                    table.set_titles(prettytable::Row::new(["Alliance", "Teams", "OPR Sum", "Finalist", "Champion"].iter().map(|c| prettytable::Cell::new(c)).collect()));
+                    for (o, teams) in outlooks.iter().zip(alliances.iter()) {
+                        table.add_row(prettytable::Row::new([
+                            o.seed.to_string(),
+                            teams.iter().map(|t| t.number().to_string()).collect::<Vec<_>>().join(" "),
+                            format!("{:.1}", o.strength),
+                            format!("{:.1}%", o.finalist * 100.0),
+                            format!("{:.1}%", o.champion * 100.0),
+                        ].iter().map(|c| prettytable::Cell::new(c)).collect()));
+                    }
+                    table.printstd();
                 },
                 ("compare", Some(sub_matches)) => {
-                    let info = the_blue_alliance::event::Event::from_key(&tba, event).wait().unwrap();
+                    let info = the_blue_alliance::event::Event::from_key(&tba, &event).wait().unwrap();
                     
-                    let event2 = sub_matches.value_of("EVENT2").expect("No event specified.");
-                    let info2 = the_blue_alliance::event::Event::from_key(&tba, event2).wait().unwrap();
+                    let event2 = value_t!(sub_matches, "EVENT2", EventKey).unwrap_or_else(|e| e.exit());
+                    let info2 = the_blue_alliance::event::Event::from_key(&tba, &event2).wait().unwrap();
 
                     println!("Common teams: ");
                     
@@ -205,11 +932,238 @@ fn main() {
                 _ => panic!("Invalid or missing subcommand!"), 
             }
         },
+        ("season", Some(sub_matches)) => {
+            let year = selected_year(&matches, time);
+
+            match sub_matches.subcommand() {
+                ("opr", Some(sub_matches)) => {
+                    let scope = if let Some(district) = sub_matches.value_of("district") {
+                        opr::Scope::District(district.to_lowercase())
+                    } else if sub_matches.is_present("division") {
+                        opr::Scope::Division(value_t!(sub_matches, "division", EventKey).unwrap_or_else(|e| e.exit()))
+                    } else {
+                        opr::Scope::Year(year)
+                    };
+                    let strength = ridge_strength(sub_matches);
+                    let weight: Box<dyn Fn(&the_blue_alliance::matches::Match) -> f64> = if sub_matches.is_present("half-life") {
+                        Box::new(opr::recency_weight(value_t!(sub_matches, "half-life", f64).unwrap_or_else(|e| e.exit()), time))
+                    } else {
+                        Box::new(|_| 1.0)
+                    };
+
+                    match opr::season_table(&tba, &scope, time, participation, strength, &*weight, sub_matches.is_present("errors")) {
+                        Ok(oprs) => print_component_table(&oprs, sub_matches.is_present("errors")),
+                        Err(e) => println!("Cannot compute OPRs: {}", e),
+                    }
+                },
+                _ => panic!("Invalid or missing subcommand!"),
+            }
+        },
+        ("elo", Some(sub_matches)) => {
+            let year = selected_year(&matches, time);
+            let seasons = value_t!(sub_matches, "seasons", i32).unwrap_or_else(|e| e.exit());
+            let years: Vec<i32> = (year - seasons + 1..=year).collect();
+
+            let mut config = elo::EloConfig::default();
+            if sub_matches.is_present("k") {
+                config.k = value_t!(sub_matches, "k", f64).unwrap_or_else(|e| e.exit());
+            }
+            if sub_matches.is_present("playoff-weight") {
+                config.playoff_weight = value_t!(sub_matches, "playoff-weight", f64).unwrap_or_else(|e| e.exit());
+            }
+            if sub_matches.is_present("regression") {
+                config.regression = value_t!(sub_matches, "regression", f64).unwrap_or_else(|e| e.exit());
+            }
+
+            let teams: Option<HashSet<TeamKey>> = if sub_matches.is_present("event") {
+                let event = value_t!(sub_matches, "event", EventKey).unwrap_or_else(|e| e.exit());
+                let info = the_blue_alliance::event::Event::from_key(&tba, &event).wait().unwrap();
+                Some(info.teams(&tba).wait().unwrap().into_iter().map(|t| t.key).collect())
+            } else {
+                None
+            };
+
+            let ratings = elo::ratings_at(&tba, &years, time, config, participation, None);
+
+            let mut table = prettytable::Table::new();
+            table.set_titles(prettytable::Row::new(["Rank", "Team", "Elo"].iter().map(|c| prettytable::Cell::new(c)).collect()));
+            for (rank, (team, rating)) in ratings.sorted().into_iter()
+                .filter(|(t, _)| match teams {
+                    Some(ref teams) => teams.contains(*t),
+                    None => true,
+                })
+                .enumerate() {
+                table.add_row(prettytable::Row::new(vec![
+                    prettytable::Cell::new(&(rank + 1).to_string()),
+                    prettytable::Cell::new(team.as_str()),
+                    prettytable::Cell::new(&format!("{:.0}", rating)),
+                ]));
+            }
+            table.printstd();
+        },
+        ("backtest", Some(sub_matches)) => {
+            let models = if sub_matches.value_of("model") == Some("all") {
+                predict::Model::ALL.to_vec()
+            } else {
+                vec![value_t!(sub_matches, "model", predict::Model).unwrap_or_else(|e| e.exit())]
+            };
+            let strength = ridge_strength(sub_matches);
+
+            let scores = if sub_matches.is_present("event") {
+                let event = value_t!(sub_matches, "event", EventKey).unwrap_or_else(|e| e.exit());
+                let info = the_blue_alliance::event::Event::from_key(&tba, &event).wait().unwrap();
+                let mut event_matches = info.matches(&tba).wait().unwrap();
+                event_matches.retain(|m| state::played_before(m, time));
+                let elo = if models.contains(&predict::Model::Elo) {
+                    let start = chrono::Utc.from_utc_date(&info.start_date).and_hms(0, 0, 0);
+                    Some(elo::ratings_at(&tba, &[info.year - 1, info.year], start, elo::EloConfig::default(), participation, Some(&info.key)))
+                } else {
+                    None
+                };
+                backtest::backtest_event(info.year, &event_matches, participation, strength, &models, elo.as_ref())
+            } else {
+                let year = selected_year(&matches, time);
+                backtest::backtest_season(&tba, year, time, participation, strength, &models)
+            };
+
+            let mut table = prettytable::Table::new();
+            table.set_titles(prettytable::Row::new(["Model", "Matches", "Brier", "Accuracy"].iter().map(|c| prettytable::Cell::new(c)).collect()));
+            for (model, score) in &scores {
+                table.add_row(prettytable::Row::new([
+                    model.to_string(),
+                    score.matches.to_string(),
+                    score.brier().map(|b| format!("{:.4}", b)).unwrap_or_else(|| "N/A".to_owned()),
+                    score.accuracy().map(|a| format!("{:.1}%", a * 100.0)).unwrap_or_else(|| "N/A".to_owned()),
+                ].iter().map(|c| prettytable::Cell::new(c)).collect()));
+            }
+            table.printstd();
+
+            for (model, score) in &scores {
+                println!("Calibration of {}:", model);
+                let mut table = prettytable::Table::new();
+                table.set_titles(prettytable::Row::new(["Predicted", "Matches", "Mean Predicted", "Observed"].iter().map(|c| prettytable::Cell::new(c)).collect()));
+                for (lower, bin) in score.calibration() {
+                    table.add_row(prettytable::Row::new([
+                        format!("{:.0}-{:.0}%", lower * 100.0, (lower + 0.1) * 100.0),
+                        bin.matches.to_string(),
+                        format!("{:.1}%", bin.predicted() * 100.0),
+                        format!("{:.1}%", bin.observed() * 100.0),
+                    ].iter().map(|c| prettytable::Cell::new(c)).collect()));
+                }
+                table.printstd();
+            }
+        },
+        ("history", Some(sub_matches)) => {
+            let mut teams: Vec<TeamKey> = Vec::new();
+            for team in values_t!(sub_matches, "TEAMS", TeamKey).unwrap_or_else(|e| e.exit()) {
+                if !teams.contains(&team) {
+                    teams.push(team);
+                }
+            }
+            let year = if sub_matches.is_present("all-years") {
+                None
+            } else {
+                Some(selected_year(&matches, time))
+            };
+
+            let pairings = history::history(&tba, &teams, year, time, participation);
+
+            let record = |r: history::Record| format!("{}-{}-{}", r.wins, r.losses, r.ties);
+            let scores = |s: Option<(f64, f64)>| s.map(|(a, b)| format!("{:.1} - {:.1}", a, b)).unwrap_or_else(|| "N/A".to_owned());
+            let mut table = prettytable::Table::new();
+            table.set_titles(prettytable::Row::new(["Teams", "Together", "Avg Score", "Against", "Avg Score"].iter().map(|c| prettytable::Cell::new(c)).collect()));
+            for p in &pairings {
+                table.add_row(prettytable::Row::new([
+                    format!("{} & {}", p.first.number(), p.second.number()),
+                    record(p.record(true)),
+                    scores(p.average_scores(true)),
+                    record(p.record(false)),
+                    scores(p.average_scores(false)),
+                ].iter().map(|c| prettytable::Cell::new(c)).collect()));
+            }
+            table.printstd();
+            println!("Records are wins-losses-ties from the first team's side. Scores are its alliance's, then its opponents'.");
+
+            if sub_matches.is_present("matches") {
+                for p in pairings.iter().filter(|p| !p.meetings.is_empty()) {
+                    println!("{} and {}:", p.first, p.second);
+                    let mut table = prettytable::Table::new();
+                    table.set_titles(prettytable::Row::new(["Match", "Together", "Result", "Score"].iter().map(|c| prettytable::Cell::new(c)).collect()));
+                    for m in &p.meetings {
+                        table.add_row(prettytable::Row::new([
+                            m.key.to_string(),
+                            if m.together { "Yes" } else { "No" }.to_owned(),
+                            if m.outcome > 0.5 { "Win" } else if m.outcome < 0.5 { "Loss" } else { "Tie" }.to_owned(),
+                            format!("{} - {}", m.scores.0, m.scores.1),
+                        ].iter().map(|c| prettytable::Cell::new(c)).collect()));
+                    }
+                    table.printstd();
+                }
+            }
+        },
         ("kiosk", Some(sub_matches)) => {
-            let event = sub_matches.value_of("EVENT").expect("No event specified.");
-            ::kiosk::run(&event, &tba).unwrap();
+            let event = value_t!(sub_matches, "EVENT", EventKey).unwrap_or_else(|e| e.exit());
+            let strength = ridge_strength(sub_matches);
+            ::kiosk::run(&event, &tba, participation, strength).unwrap();
         },
         _ => panic!("Invalid or missing subcommand!"),
     }
     
 }
+
+/// The `--ridge` argument of commands that solve OPRs.
+fn ridge_arg() -> Arg<'static, 'static> {
+    Arg::with_name("ridge")
+        .long("ridge")
+        .value_name("STRENGTH")
+        .help("Regularize OPRs toward the event mean with this strength. Defaults to only regularizing when there are too few matches.")
+        .takes_value(true)
+}
+
+/// The `--ridge` strength, or `None` to only regularize when there are too few matches.
+fn ridge_strength(matches: &clap::ArgMatches) -> Option<f64> {
+    if matches.is_present("ridge") {
+        Some(value_t!(matches, "ridge", f64).unwrap_or_else(|e| e.exit()))
+    } else {
+        None
+    }
+}
+
+/// Exits with a clap error for a value that parsed but doesn't apply.
+fn invalid_value(message: &str) -> ! {
+    clap::Error::with_description(message, clap::ErrorKind::InvalidValue).exit()
+}
+
+/// The global `--year`, defaulting to the year of `time`.
+fn selected_year(matches: &clap::ArgMatches, time: chrono::DateTime<chrono::Utc>) -> i32 {
+    if matches.is_present("year") {
+        value_t!(matches, "year", i32).unwrap_or_else(|e| e.exit())
+    } else {
+        time.year()
+    }
+}
+
+/// Prints a component table ranked by its first column, followed by each column's fit.
+fn print_component_table(oprs: &opr::ComponentTable, errors: bool) {
+    let mut table = prettytable::Table::new();
+    table.set_titles(prettytable::Row::new(["Rank", "Team"].iter().chain(oprs.columns.iter()).map(|c| prettytable::Cell::new(c)).collect()));
+    for (rank, (team, row)) in oprs.sorted_by(0).into_iter().enumerate() {
+        table.add_row(prettytable::Row::new([(rank + 1).to_string(), team.to_string()].iter().cloned()
+            .chain(row.iter().map(|e| if e.value.is_nan() {
+                "N/A".to_owned()
+            } else if errors {
+                format!("{:.1} ± {:.1}", e.value, opr::Z_95 * e.standard_error)
+            } else {
+                format!("{:.1}", e.value)
+            }))
+            .map(|v| prettytable::Cell::new(&v))
+            .collect()));
+    }
+    table.printstd();
+
+    for (name, stats) in oprs.columns.iter().zip(oprs.stats.iter()) {
+        if let Some(stats) = stats {
+            println!("{}: R² {:.2}, RMSE {:.1}, residual SD {:.1} over {} alliances", name, stats.r_squared, stats.rmse, stats.residual_sd, stats.alliances);
+        }
+    }
+}
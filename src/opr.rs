@@ -1,94 +1,957 @@
 
-use std::collections::HashMap;
-
-pub fn oprs_from_matches(mut matches: Vec<the_blue_alliance::matches::Match>) -> HashMap<String, f32> {
-    matches.retain(|m| m.score_breakdown.is_some());
-
-    let scores: nalgebra::DVector<f32> = nalgebra::DVector::from_iterator(matches.len() * 2,
-        matches.iter()
-            .flat_map(|m| 
-                vec![
-                    m.score_breakdown.as_ref().unwrap().total_points(true) as f32, 
-                    m.score_breakdown.as_ref().unwrap().total_points(false) as f32
-                ].into_iter()
-            )
-    );
-    
-    let mut teams_list: Vec<_> = matches.iter().flat_map(|m| m.team_keys().unwrap().into_iter()).collect();
-    teams_list.sort_unstable();
-    teams_list.dedup();
-    let teams_list = teams_list;
-
-    let teams: nalgebra::DMatrix<f32> = nalgebra::DMatrix::from_iterator(teams_list.len(), matches.len() * 2, 
-        matches.iter()
-        .flat_map(|m| {
-            let teams_red = &m.alliances.as_ref().unwrap().red.team_keys;
-            let red = teams_list.iter().map(|t| if teams_red.contains(t) {1.0} else {0.0}).collect::<Vec<_>>().into_iter();
-            let teams_blue = &m.alliances.as_ref().unwrap().blue.team_keys;
-            let blue = teams_list.iter().map(|t| if teams_blue.contains(t) {1.0} else {0.0}).collect::<Vec<_>>().into_iter();
-            red.chain(blue)
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::sync::{Arc, OnceLock};
+use chrono::{DateTime, Utc};
+use nalgebra::{DMatrix, DVector};
+use the_blue_alliance::matches::{AllianceColor, Match, ScoreBreakdownAlliance};
+use the_blue_alliance::keys::{EventKey, TeamKey};
+use the_blue_alliance::event::Event;
+use futures::Future;
+use state::{self, Participation};
+
+/// A per-alliance quantity that can be split into per-team contributions.
+pub struct Component {
+    pub name: &'static str,
+    pub metric: fn(&Match, AllianceColor) -> Option<f64>,
+}
+
+/// The components worth computing for a season, starting with OPR, DPR and CCWM.
+pub fn components(year: i32) -> Vec<Component> {
+    let mut components = vec![
+        Component { name: "OPR", metric: |m, c| m.score_for(c).map(|s| s as f64) },
+        Component { name: "DPR", metric: |m, c| m.score_for(c.opponent()).map(|s| s as f64) },
+        Component { name: "CCWM", metric: |m, c| Some((m.score_for(c)? - m.score_for(c.opponent())?) as f64) },
+        Component { name: "Auto", metric: |m, c| m.breakdown_for(c).map(|b| b.auto_points() as f64) },
+        Component { name: "Teleop", metric: |m, c| m.breakdown_for(c).map(|b| b.teleop_points() as f64) },
+    ];
+
+    components.extend(match year {
+        2015 => vec![
+            Component { name: "Totes", metric: |m, c| match m.breakdown_for(c)? {
+                ScoreBreakdownAlliance::Year2015(d) => Some(d.tote_points as f64),
+                _ => None,
+            }},
+            Component { name: "Containers", metric: |m, c| match m.breakdown_for(c)? {
+                ScoreBreakdownAlliance::Year2015(d) => Some(d.container_points as f64),
+                _ => None,
+            }},
+            Component { name: "Litter", metric: |m, c| match m.breakdown_for(c)? {
+                ScoreBreakdownAlliance::Year2015(d) => Some(d.litter_points as f64),
+                _ => None,
+            }},
+        ],
+        2016 => vec![
+            Component { name: "Boulders", metric: |m, c| match m.breakdown_for(c)? {
+                ScoreBreakdownAlliance::Year2016(d) => Some((d.auto_boulder_points + d.teleop_boulder_points) as f64),
+                _ => None,
+            }},
+            Component { name: "Crossings", metric: |m, c| match m.breakdown_for(c)? {
+                ScoreBreakdownAlliance::Year2016(d) => Some((d.auto_crossing_points + d.teleop_crossing_points) as f64),
+                _ => None,
+            }},
+            Component { name: "Tower", metric: |m, c| match m.breakdown_for(c)? {
+                ScoreBreakdownAlliance::Year2016(d) => Some((d.teleop_challenge_points + d.teleop_scale_points) as f64),
+                _ => None,
+            }},
+        ],
+        2017 => vec![
+            Component { name: "Fuel", metric: |m, c| match m.breakdown_for(c)? {
+                ScoreBreakdownAlliance::Year2017(d) => Some((d.auto_fuel_points + d.teleop_fuel_points) as f64),
+                _ => None,
+            }},
+            Component { name: "Rotors", metric: |m, c| match m.breakdown_for(c)? {
+                ScoreBreakdownAlliance::Year2017(d) => Some((d.auto_rotor_points + d.teleop_rotor_points) as f64),
+                _ => None,
+            }},
+            Component { name: "Takeoff", metric: |m, c| match m.breakdown_for(c)? {
+                ScoreBreakdownAlliance::Year2017(d) => Some(d.teleop_takeoff_points as f64),
+                _ => None,
+            }},
+        ],
+        2018 => vec![
+            Component { name: "Ownership", metric: |m, c| match m.breakdown_for(c)? {
+                ScoreBreakdownAlliance::Year2018(d) => Some((d.auto_ownership_points + d.teleop_ownership_points) as f64),
+                _ => None,
+            }},
+            Component { name: "Vault", metric: |m, c| match m.breakdown_for(c)? {
+                ScoreBreakdownAlliance::Year2018(d) => Some(d.vault_points as f64),
+                _ => None,
+            }},
+            Component { name: "Endgame", metric: |m, c| match m.breakdown_for(c)? {
+                ScoreBreakdownAlliance::Year2018(d) => Some(d.endgame_points as f64),
+                _ => None,
+            }},
+        ],
+        2019 => vec![
+            Component { name: "Cargo", metric: |m, c| match m.breakdown_for(c)? {
+                ScoreBreakdownAlliance::Year2019(d) => Some(d.cargo_points as f64),
+                _ => None,
+            }},
+            Component { name: "Panel", metric: |m, c| match m.breakdown_for(c)? {
+                ScoreBreakdownAlliance::Year2019(d) => Some(d.hatch_panel_points as f64),
+                _ => None,
+            }},
+            Component { name: "Climb", metric: |m, c| match m.breakdown_for(c)? {
+                ScoreBreakdownAlliance::Year2019(d) => Some(d.hab_climb_points as f64),
+                _ => None,
+            }},
+            Component { name: "Sandstorm", metric: |m, c| match m.breakdown_for(c)? {
+                ScoreBreakdownAlliance::Year2019(d) => Some(d.sand_storm_bonus_points as f64),
+                _ => None,
+            }},
+        ],
+        _ => vec![],
+    });
+
+    components.push(Component { name: "Fouls", metric: |m, c| m.breakdown_for(c).map(|b| b.foul_points() as f64) });
+    components
+}
+
+/// The ranking point bonuses for a season, as components that are 1 when an alliance earned
+/// the bonus and 0 when it did not. Each team's value is roughly how much it adds to an
+/// alliance's chance of earning the bonus.
+pub fn bonuses(year: i32) -> Vec<Component> {
+    match year {
+        2016 => vec![
+            Component { name: "Breach", metric: |m, c| match m.breakdown_for(c)? {
+                ScoreBreakdownAlliance::Year2016(d) => Some(d.teleop_defenses_breached as f64),
+                _ => None,
+            }},
+            Component { name: "Capture", metric: |m, c| match m.breakdown_for(c)? {
+                ScoreBreakdownAlliance::Year2016(d) => Some(d.teleop_tower_captured as f64),
+                _ => None,
+            }},
+        ],
+        2017 => vec![
+            Component { name: "kPa", metric: |m, c| match m.breakdown_for(c)? {
+                ScoreBreakdownAlliance::Year2017(d) => Some(if d.kpa_ranking_point_achieved { 1.0 } else { 0.0 }),
+                _ => None,
+            }},
+            Component { name: "Rotors", metric: |m, c| match m.breakdown_for(c)? {
+                ScoreBreakdownAlliance::Year2017(d) => Some(if d.rotor_ranking_point_achieved { 1.0 } else { 0.0 }),
+                _ => None,
+            }},
+        ],
+        2018 => vec![
+            Component { name: "Auto Quest", metric: |m, c| match m.breakdown_for(c)? {
+                ScoreBreakdownAlliance::Year2018(d) => Some(if d.auto_quest_ranking_point { 1.0 } else { 0.0 }),
+                _ => None,
+            }},
+            Component { name: "Face the Boss", metric: |m, c| match m.breakdown_for(c)? {
+                ScoreBreakdownAlliance::Year2018(d) => Some(if d.face_the_boss_ranking_point { 1.0 } else { 0.0 }),
+                _ => None,
+            }},
+        ],
+        2019 => vec![
+            Component { name: "Rocket", metric: |m, c| match m.breakdown_for(c)? {
+                ScoreBreakdownAlliance::Year2019(d) => Some(if d.complete_rocket_ranking_point { 1.0 } else { 0.0 }),
+                _ => None,
+            }},
+            Component { name: "HAB Docking", metric: |m, c| match m.breakdown_for(c)? {
+                ScoreBreakdownAlliance::Year2019(d) => Some(if d.hab_docking_ranking_point { 1.0 } else { 0.0 }),
+                _ => None,
+            }},
+        ],
+        _ => vec![],
+    }
+}
+
+/// Why OPRs could not be computed.
+#[derive(Debug, Clone, PartialEq)]
+pub enum OprError {
+    /// There are no played matches to solve from.
+    NoMatches,
+    /// The matches played so far do not separate every team's contribution, which is normal
+    /// early in qualifications.
+    RankDeficient { teams: usize, rank: usize },
+    /// The district key is malformed or names no district.
+    UnknownDistrict(String),
+}
+
+impl fmt::Display for OprError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OprError::NoMatches => write!(f, "No matches have been played"),
+            OprError::RankDeficient { teams, rank } => write!(f, "Not enough matches to separate {} teams (rank {})", teams, rank),
+            OprError::UnknownDistrict(key) => write!(f, "Cannot find district {}", key),
+        }
+    }
+}
+
+/// The strength used when OPRs have to be regularized because the matches so far do not
+/// determine every team. Roughly the ratio of the match-to-match noise in an alliance's score
+/// to the spread of team OPRs.
+pub const DEFAULT_STRENGTH: f64 = 2.0;
+
+/// What a regularized solve shrinks each team's estimate toward.
+pub enum Prior<'p> {
+    /// Every team starts at the average per-team value of the matches being solved.
+    EventMean,
+    /// Teams start at their value in another table, such as OPRs from their previous event.
+    /// Teams missing from it start at that table's column mean, which is close to the season
+    /// mean when the table covers many events.
+    Table(&'p ComponentTable),
+}
+
+/// One alliance in one match, as the indices of the teams its score is shared between.
+struct Row {
+    match_index: usize,
+    color: AllianceColor,
+    teams: Vec<usize>,
+    weight: f64,
+}
+
+/// The factorized normal equations for a set of matches, shared between every metric solved
+/// over the same alliances.
+///
+/// Each alliance only has three teams, so `AᵀA` is accumulated directly from the alliances
+/// instead of forming the teams×alliances design matrix.
+///
+/// With a nonzero `strength` this solves the ridge (MMSE) problem `(AᵀA + λI)x = Aᵀb + λp`
+/// instead, which has a solution from the first match on and shrinks teams with few matches
+/// toward the prior `p`.
+///
+/// Matches can be weighted, e.g. by recency when pooling a whole season, which turns this into
+/// weighted least squares `AᵀWA`.
+pub struct Design<'a> {
+    matches: &'a [Match],
+    teams: Vec<TeamKey>,
+    rows: Vec<Row>,
+    strength: f64,
+    factor: Arc<Factor>,
+}
+
+impl<'a> Design<'a> {
+    /// Builds the design from every played match. A `strength` of zero gives plain least
+    /// squares OPRs.
+    pub fn new(matches: &'a [Match], participation: Participation, strength: f64) -> Result<Design<'a>, OprError> {
+        Design::weighted(matches, participation, strength, &|_| 1.0)
+    }
+
+    /// Like `new`, but each match's alliances count `weight(match)` times.
+    pub fn weighted(matches: &'a [Match], participation: Participation, strength: f64, weight: &dyn Fn(&Match) -> f64) -> Result<Design<'a>, OprError> {
+        let alliances: Vec<(usize, AllianceColor, Vec<&TeamKey>)> = matches.iter().enumerate()
+            .filter(|(_, m)| AllianceColor::BOTH.iter().all(|&c| m.score_for(c).is_some()))
+            .flat_map(|(match_index, m)| AllianceColor::BOTH.iter().map(move |&color| {
+                let alliance = m.alliances.as_ref().unwrap().get(color);
+                let teams = alliance.team_keys.iter()
+                    .filter(|t| participation.is_in_oprs(alliance, t))
+                    .collect();
+                (match_index, color, teams)
+            }))
+            .filter(|(_, _, teams): &(_, _, Vec<_>)| !teams.is_empty())
+            .collect();
+
+        if alliances.is_empty() {
+            return Err(OprError::NoMatches);
+        }
+
+        let mut teams: Vec<TeamKey> = alliances.iter().flat_map(|(_, _, teams)| teams.iter().cloned().cloned()).collect();
+        teams.sort_unstable();
+        teams.dedup();
+
+        let rows: Vec<Row> = {
+            let index: HashMap<&TeamKey, usize> = teams.iter().enumerate().map(|(i, t)| (t, i)).collect();
+            alliances.into_iter()
+                .map(|(match_index, color, alliance)| Row {
+                    match_index,
+                    color,
+                    teams: alliance.into_iter().map(|t| index[t]).collect(),
+                    weight: weight(&matches[match_index]),
+                })
+                .collect()
+        };
+
+        let factor = Arc::new(Factor::new(normal_matrix(teams.len(), rows.iter(), strength))?);
+
+        Ok(Design {
+            matches,
+            teams,
+            rows,
+            strength,
+            factor,
+        })
+    }
+
+    /// Plain OPRs if the matches determine every team, otherwise regularized with
+    /// `DEFAULT_STRENGTH`. A given `strength` is always used as is.
+    pub fn for_matches(matches: &'a [Match], participation: Participation, strength: Option<f64>) -> Result<Design<'a>, OprError> {
+        Design::for_weighted_matches(matches, participation, strength, &|_| 1.0)
+    }
+
+    /// Like `for_matches`, but each match's alliances count `weight(match)` times.
+    pub fn for_weighted_matches(matches: &'a [Match], participation: Participation, strength: Option<f64>, weight: &dyn Fn(&Match) -> f64) -> Result<Design<'a>, OprError> {
+        match strength {
+            Some(strength) => Design::weighted(matches, participation, strength, weight),
+            None => Design::weighted(matches, participation, 0.0, weight).or_else(|e| match e {
+                OprError::RankDeficient { .. } => Design::weighted(matches, participation, DEFAULT_STRENGTH, weight),
+                e => Err(e),
+            }),
+        }
+    }
+
+    pub fn teams(&self) -> &[TeamKey] {
+        &self.teams
+    }
+
+    /// Solves for each team's contribution to `metric`, which gives a value for each alliance
+    /// in a match, and estimates how uncertain each team's value is. Alliances the metric has
+    /// no value for are left out, which needs its own factorization. A regularized design
+    /// shrinks toward the event mean.
+    pub fn fit<F>(&self, metric: F) -> Result<Fit, OprError>
+        where F: Fn(&Match, AllianceColor) -> Option<f64>
+    {
+        self.fit_with_prior(metric, &HashMap::new())
+    }
+
+    /// Like `fit`, but a regularized design shrinks each team toward its value in `prior`.
+    /// Teams missing from `prior` are shrunk toward the event mean.
+    ///
+    /// The residual variance is estimated from the alliances' residuals, using the effective
+    /// degrees of freedom of the (possibly regularized) fit, and scaled by `(AᵀA + λI)⁻¹` to give
+    /// the covariance of the estimates. For regularized fits this is an approximation, and
+    /// needs the trace of the inverse.
+    pub fn fit_with_prior<F>(&self, metric: F, prior: &HashMap<TeamKey, f64>) -> Result<Fit, OprError>
+        where F: Fn(&Match, AllianceColor) -> Option<f64>
+    {
+        let Solved { rows, factor, solution } = self.solve_rows(metric, prior)?;
+
+        let n = rows.len() as f64;
+        let total_weight: f64 = rows.iter().map(|(r, _)| r.weight).sum();
+        let mean = rows.iter().map(|(r, v)| r.weight * v).sum::<f64>() / total_weight;
+        let rss: f64 = rows.iter()
+            .map(|(r, v)| (r.weight, v - r.teams.iter().map(|&i| solution[i]).sum::<f64>()))
+            .map(|(w, e)| w * e * e)
+            .sum();
+        let tss: f64 = rows.iter().map(|(r, v)| r.weight * (v - mean) * (v - mean)).sum();
+        let parameters = if self.strength > 0.0 {
+            self.teams.len() as f64 - self.strength * factor.inverse_trace()
+        } else {
+            self.teams.len() as f64
+        };
+        let variance = if n > parameters { rss / (n - parameters) } else { f64::NAN };
+
+        Ok(Fit {
+            teams: self.teams.clone(),
+            values: solution,
+            factor,
+            variance,
+            stats: FitStats {
+                r_squared: 1.0 - rss / tss,
+                rmse: (rss / total_weight).sqrt(),
+                residual_sd: variance.sqrt(),
+                alliances: rows.len(),
+            },
         })
-    ).transpose();
-
-    let transpose = teams.transpose();
-
-    let teams_transpose = transpose.clone() * teams;
-    let scores_transpose = transpose * scores;
-
-    let decomp = nalgebra::linalg::QR::new(teams_transpose);
-    let solution = decomp.solve(&scores_transpose);
-    if let Some(solution) = solution {
-        teams_list.into_iter().cloned().zip(solution.into_iter().cloned()).collect()
-    } else {
-        HashMap::new()
-    }
-
-}
-
-pub fn dprs_from_matches(mut matches: Vec<the_blue_alliance::matches::Match>) -> HashMap<String, f32> {
-    matches.retain(|m| m.score_breakdown.is_some());
-
-    let scores: nalgebra::DVector<f32> = nalgebra::DVector::from_iterator(matches.len() * 2,
-        matches.iter()
-            .flat_map(|m| 
-                vec![
-                    m.score_breakdown.as_ref().unwrap().total_points(false) as f32, 
-                    m.score_breakdown.as_ref().unwrap().total_points(true) as f32
-                ].into_iter()
-            )
-    );
-    
-    let mut teams_list: Vec<_> = matches.iter().flat_map(|m| m.team_keys().unwrap().into_iter()).collect();
-    teams_list.sort_unstable();
-    teams_list.dedup();
-    let teams_list = teams_list;
-
-    let teams: nalgebra::DMatrix<f32> = nalgebra::DMatrix::from_iterator(teams_list.len(), matches.len() * 2, 
-        matches.iter()
-        .flat_map(|m| {
-            let teams_red = &m.alliances.as_ref().unwrap().red.team_keys;
-            let red = teams_list.iter().map(|t| if teams_red.contains(t) {1.0} else {0.0}).collect::<Vec<_>>().into_iter();
-            let teams_blue = &m.alliances.as_ref().unwrap().blue.team_keys;
-            let blue = teams_list.iter().map(|t| if teams_blue.contains(t) {1.0} else {0.0}).collect::<Vec<_>>().into_iter();
-            red.chain(blue)
+    }
+
+    /// Solves the normal equations for the alliances `metric` has a value for.
+    fn solve_rows<F>(&self, metric: F, prior: &HashMap<TeamKey, f64>) -> Result<Solved<'_>, OprError>
+        where F: Fn(&Match, AllianceColor) -> Option<f64>
+    {
+        let rows: Vec<(&Row, f64)> = self.rows.iter()
+            .filter_map(|r| metric(&self.matches[r.match_index], r.color).map(|v| (r, v)))
+            .collect();
+        if rows.is_empty() {
+            return Err(OprError::NoMatches);
+        }
+
+        let mut rhs = normal_vector(self.teams.len(), rows.iter().cloned());
+        if self.strength > 0.0 {
+            let mean = rows.iter().map(|(r, v)| r.weight * v).sum::<f64>() / rows.iter().map(|(r, _)| r.weight * r.teams.len() as f64).sum::<f64>();
+            for (i, t) in self.teams.iter().enumerate() {
+                rhs[i] += self.strength * prior.get(t).cloned().unwrap_or(mean);
+            }
+        }
+
+        let factor = if rows.len() == self.rows.len() {
+            self.factor.clone()
+        } else {
+            Arc::new(Factor::new(normal_matrix(self.teams.len(), rows.iter().map(|(r, _)| *r), self.strength))?)
+        };
+        let solution = factor.solve(rhs);
+        Ok(Solved { rows, factor, solution })
+    }
+}
+
+/// The alliances a metric was solved over, the factorized normal matrix used, and the
+/// solution.
+struct Solved<'r> {
+    rows: Vec<(&'r Row, f64)>,
+    factor: Arc<Factor>,
+    solution: DVector<f64>,
+}
+
+/// z-score of a two sided 95% confidence interval.
+pub const Z_95: f64 = 1.959_964;
+
+/// How well a solved metric explains the alliances it was solved from.
+#[derive(Debug, Clone, Copy)]
+pub struct FitStats {
+    pub r_squared: f64,
+    /// Root mean square error of the predicted alliance values.
+    pub rmse: f64,
+    /// Estimated standard deviation of an alliance's value around its prediction.
+    pub residual_sd: f64,
+    pub alliances: usize,
+}
+
+/// A team's value for one metric and its standard error.
+#[derive(Debug, Clone, Copy)]
+pub struct Estimate {
+    pub value: f64,
+    pub standard_error: f64,
+}
+
+impl Estimate {
+    pub const MISSING: Estimate = Estimate { value: f64::NAN, standard_error: f64::NAN };
+
+    /// The 95% confidence interval of the value.
+    pub fn confidence_interval(&self) -> (f64, f64) {
+        (self.value - Z_95 * self.standard_error, self.value + Z_95 * self.standard_error)
+    }
+}
+
+/// A solved metric with the covariance of its estimates, `(AᵀA + λI)⁻¹ * variance`. The
+/// inverse is never formed, since for a whole season it is thousands of teams square; the
+/// entries a standard error needs come from the factor, which is shared between every metric
+/// solved over the same alliances.
+pub struct Fit {
+    teams: Vec<TeamKey>,
+    values: DVector<f64>,
+    factor: Arc<Factor>,
+    variance: f64,
+    pub stats: FitStats,
+}
+
+impl Fit {
+    fn index(&self, team: &TeamKey) -> Option<usize> {
+        self.teams.binary_search(team).ok()
+    }
+
+    pub fn get(&self, team: &TeamKey) -> Option<Estimate> {
+        self.index(team).map(|i| Estimate {
+            value: self.values[i],
+            standard_error: (self.factor.inverse_diagonal(i) * self.variance).sqrt(),
+        })
+    }
+
+    /// Every team's estimate. Without `errors` the standard errors are left as NaN, which
+    /// saves a triangular solve per team.
+    pub fn estimates(&self, errors: bool) -> HashMap<TeamKey, Estimate> {
+        self.teams.iter().enumerate()
+            .map(|(i, t)| (t.clone(), if errors {
+                self.get(t).unwrap()
+            } else {
+                Estimate { value: self.values[i], standard_error: f64::NAN }
+            }))
+            .collect()
+    }
+
+    /// The difference between two teams' values, `a - b`, and its standard error. Teams that
+    /// played together have correlated estimates, so this is not just the two standard errors
+    /// combined.
+    pub fn difference(&self, a: &TeamKey, b: &TeamKey) -> Option<Estimate> {
+        let (i, j) = (self.index(a)?, self.index(b)?);
+        let variance = self.factor.inverse_form(&[(i, 1.0), (j, -1.0)]) * self.variance;
+        Some(Estimate {
+            value: self.values[i] - self.values[j],
+            standard_error: variance.sqrt(),
         })
-    ).transpose();
+    }
 
-    let transpose = teams.transpose();
+    /// Whether the two teams' values differ at the 95% level.
+    pub fn distinguishable(&self, a: &TeamKey, b: &TeamKey) -> Option<bool> {
+        self.difference(a, b).map(|d| d.value.abs() > Z_95 * d.standard_error)
+    }
+}
 
-    let teams_transpose = transpose.clone() * teams;
-    let scores_transpose = transpose * scores;
+/// Accumulates `AᵀWA + λI` for the given alliances.
+fn normal_matrix<'r, I: Iterator<Item = &'r Row>>(teams: usize, rows: I, strength: f64) -> DMatrix<f64> {
+    let mut ata = DMatrix::identity(teams, teams) * strength;
+    for r in rows {
+        for &i in &r.teams {
+            for &j in &r.teams {
+                ata[(i, j)] += r.weight;
+            }
+        }
+    }
+    ata
+}
 
-    let decomp = nalgebra::linalg::QR::new(teams_transpose);
-    let solution = decomp.solve(&scores_transpose);
-    if let Some(solution) = solution {
-        teams_list.into_iter().cloned().zip(solution.into_iter().cloned()).collect()
-    } else {
-        HashMap::new()
+/// Accumulates `AᵀWb` for the given alliances and their values.
+fn normal_vector<'r, I: Iterator<Item = (&'r Row, f64)>>(teams: usize, rows: I) -> DVector<f64> {
+    let mut atb = DVector::zeros(teams);
+    for (r, value) in rows {
+        for &i in &r.teams {
+            atb[i] += r.weight * value;
+        }
     }
+    atb
+}
+
+/// Pivots smaller than this fraction of the largest diagonal entry are treated as zero.
+const PIVOT_TOLERANCE: f64 = 1e-10;
+
+/// The Cholesky factor `L` of `AᵀA + λI`, stored row-major in the matrix it was factorized
+/// from. Only the lower triangle is meaningful.
+struct Factor {
+    size: usize,
+    lower: Vec<f64>,
+    /// Diagonal entries of the inverse, computed as standard errors ask for them.
+    inverse_diagonal: Vec<OnceLock<f64>>,
 }
 
+impl Factor {
+    /// Factorizes `AᵀA`, or reports it as rank deficient. Rounding can leave a tiny positive
+    /// pivot where the matrix is really singular, so pivots below a tolerance relative to the
+    /// largest diagonal entry count as zero. Their columns are dropped and factorization
+    /// carries on, which makes the number of accepted pivots the rank.
+    fn new(ata: DMatrix<f64>) -> Result<Factor, OprError> {
+        let size = ata.nrows();
+        let tolerance = PIVOT_TOLERANCE * ata.diagonal().max();
+        // `ata` is symmetric, so its column-major storage is also row-major.
+        let mut lower = ata.as_slice().to_vec();
+        drop(ata);
+
+        let mut rank = 0;
+        for j in 0..size {
+            let row_j = &lower[j * size..j * size + j];
+            let pivot = lower[j * size + j] - dot(row_j, row_j);
+            if pivot <= tolerance {
+                for i in j..size {
+                    lower[i * size + j] = 0.0;
+                }
+                continue;
+            }
+            rank += 1;
+            let pivot = pivot.sqrt();
+            lower[j * size + j] = pivot;
+
+            for i in j + 1..size {
+                let (head, tail) = lower.split_at_mut(i * size);
+                let row_j = &head[j * size..j * size + j];
+                tail[j] = (tail[j] - dot(&tail[..j], row_j)) / pivot;
+            }
+        }
+
+        if rank < size {
+            return Err(OprError::RankDeficient { teams: size, rank });
+        }
+        Ok(Factor {
+            size,
+            lower,
+            inverse_diagonal: (0..size).map(|_| OnceLock::new()).collect(),
+        })
+    }
 
-pub fn ccwms_from_oprs_and_dprs(oprs: HashMap<String, f32>, dprs: HashMap<String, f32>) -> HashMap<String, f32> {
-    oprs.into_iter().map(|(t, opr)| (t.clone(), opr - dprs[&t])).collect()
-}
\ No newline at end of file
+    fn row(&self, i: usize) -> &[f64] {
+        &self.lower[i * self.size..(i + 1) * self.size]
+    }
+
+    /// Solves `LLᵀx = b`.
+    fn solve(&self, mut b: DVector<f64>) -> DVector<f64> {
+        for i in 0..self.size {
+            let row = self.row(i);
+            b[i] = (b[i] - dot(&row[..i], &b.as_slice()[..i])) / row[i];
+        }
+        for i in (0..self.size).rev() {
+            let row = self.row(i);
+            b[i] /= row[i];
+            for (k, &l) in row[..i].iter().enumerate() {
+                b[k] -= l * b[i];
+            }
+        }
+        b
+    }
+
+    /// `vᵀ(LLᵀ)⁻¹v` for a sparse `v`, as `|L⁻¹v|²`. The forward solve starts at the first
+    /// nonzero entry, so this costs about half a dense solve on average.
+    fn inverse_form(&self, v: &[(usize, f64)]) -> f64 {
+        let start = match v.iter().map(|&(i, _)| i).min() {
+            Some(start) => start,
+            None => return 0.0,
+        };
+        let mut z = vec![0.0; self.size - start];
+        for &(i, value) in v {
+            z[i - start] += value;
+        }
+        for i in start..self.size {
+            let row = &self.row(i)[start..];
+            z[i - start] = (z[i - start] - dot(&row[..i - start], &z[..i - start])) / row[i - start];
+        }
+        dot(&z, &z)
+    }
+
+    /// One diagonal entry of `(LLᵀ)⁻¹`.
+    fn inverse_diagonal(&self, i: usize) -> f64 {
+        *self.inverse_diagonal[i].get_or_init(|| self.inverse_form(&[(i, 1.0)]))
+    }
+
+    /// The trace of `(LLᵀ)⁻¹`, which takes every diagonal entry.
+    fn inverse_trace(&self) -> f64 {
+        (0..self.size).map(|i| self.inverse_diagonal(i)).sum()
+    }
+}
+
+fn dot(a: &[f64], b: &[f64]) -> f64 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+/// Component OPRs for every team, one column per component.
+pub struct ComponentTable {
+    pub columns: Vec<&'static str>,
+    pub values: HashMap<TeamKey, Vec<Estimate>>,
+    /// Fit statistics for each column, if it was solved from matches.
+    pub stats: Vec<Option<FitStats>>,
+}
+
+impl ComponentTable {
+    /// Combines separately solved components into one table. Teams missing from a component
+    /// get `Estimate::MISSING` in that column. Without `errors` standard errors are NaN.
+    pub fn from_columns(columns: Vec<(&'static str, Fit)>, errors: bool) -> ComponentTable {
+        let mut values: HashMap<TeamKey, Vec<Estimate>> = HashMap::new();
+        for (i, (_, fit)) in columns.iter().enumerate() {
+            for (team, estimate) in fit.estimates(errors) {
+                let row = values.entry(team).or_insert_with(|| vec![Estimate::MISSING; columns.len()]);
+                row[i] = estimate;
+            }
+        }
+
+        ComponentTable {
+            stats: columns.iter().map(|(_, fit)| Some(fit.stats)).collect(),
+            columns: columns.into_iter().map(|(name, _)| name).collect(),
+            values,
+        }
+    }
+
+    /// The mean of a column over every team that has a value in it.
+    pub fn column_mean(&self, column: usize) -> Option<f64> {
+        let values: Vec<f64> = self.values.values().map(|row| row[column].value).filter(|v| !v.is_nan()).collect();
+        if values.is_empty() {
+            None
+        } else {
+            Some(values.iter().sum::<f64>() / values.len() as f64)
+        }
+    }
+
+    /// The standard deviation of a column over every team that has a value in it.
+    pub fn column_sd(&self, column: usize) -> Option<f64> {
+        let mean = self.column_mean(column)?;
+        let values: Vec<f64> = self.values.values().map(|row| row[column].value).filter(|v| !v.is_nan()).collect();
+        Some((values.iter().map(|v| (v - mean) * (v - mean)).sum::<f64>() / values.len() as f64).sqrt())
+    }
+
+    /// The index of the column for the component `name`.
+    pub fn column(&self, name: &str) -> Option<usize> {
+        self.columns.iter().position(|&c| c == name)
+    }
+
+    /// A team's value in a column, or the column mean if the team has none.
+    pub fn value_or_mean(&self, team: &TeamKey, column: usize) -> Option<f64> {
+        self.values.get(team).map(|row| row[column].value).filter(|v| !v.is_nan())
+            .or_else(|| self.column_mean(column))
+    }
+
+    /// Per-team priors for the component `name`, for every team in `teams`. Teams missing from
+    /// this table get the column mean.
+    fn prior_for(&self, name: &str, teams: &[TeamKey]) -> HashMap<TeamKey, f64> {
+        let column = match self.column(name) {
+            Some(column) => column,
+            None => return HashMap::new(),
+        };
+        teams.iter()
+            .filter_map(|t| self.value_or_mean(t, column).map(|v| (t.clone(), v)))
+            .collect()
+    }
+
+    /// The rows of the table, best first by the given column.
+    pub fn sorted_by(&self, column: usize) -> Vec<(&TeamKey, &Vec<Estimate>)> {
+        let mut rows: Vec<_> = self.values.iter().collect();
+        rows.sort_unstable_by(|(_, a), (_, b)| b[column].value.partial_cmp(&a[column].value).unwrap_or(std::cmp::Ordering::Equal));
+        rows
+    }
+}
+
+pub fn component_table(design: &Design, components: &[Component], prior: &Prior) -> Result<ComponentTable, OprError> {
+    Ok(ComponentTable::from_columns(columns(design, components, prior)?, true))
+}
+
+/// Solves every component over `design`.
+fn columns(design: &Design, components: &[Component], prior: &Prior) -> Result<Vec<(&'static str, Fit)>, OprError> {
+    components.iter()
+        .map(|c| {
+            let prior = match prior {
+                Prior::EventMean => HashMap::new(),
+                Prior::Table(table) => table.prior_for(c.name, design.teams()),
+            };
+            design.fit_with_prior(c.metric, &prior).map(|fit| (c.name, fit))
+        })
+        .collect()
+}
+
+/// Builds a table of each team's OPRs at the last event it finished before `event` started.
+/// Used as a prior for regularized OPRs.
+pub fn previous_event_table(tba: &the_blue_alliance::TBA, event: &the_blue_alliance::event::Event, participation: Participation, strength: f64) -> ComponentTable {
+    let teams = event.teams(tba).wait().unwrap();
+    let components = components(event.year);
+    let mut tables: HashMap<EventKey, Option<ComponentTable>> = HashMap::new();
+    let mut rows: HashMap<TeamKey, Vec<Estimate>> = HashMap::new();
+
+    for team in teams {
+        let previous = the_blue_alliance::event::Event::for_team_key(tba, &team.key).wait().unwrap()
+            .into_iter()
+            .filter(|e| e.year == event.year && e.end_date < event.start_date)
+            .max();
+
+        if let Some(previous) = previous {
+            let table = tables.entry(previous.key.clone()).or_insert_with(|| {
+                let matches = previous.matches(tba).wait().unwrap();
+                Design::new(&matches, participation, strength)
+                    .and_then(|design| component_table(&design, &components, &Prior::EventMean))
+                    .ok()
+            });
+            if let Some(row) = table.as_ref().and_then(|t| t.values.get(&team.key)) {
+                rows.insert(team.key.clone(), row.clone());
+            }
+        }
+    }
+
+    ComponentTable {
+        columns: components.iter().map(|c| c.name).collect(),
+        values: rows,
+        stats: vec![None; components.len()],
+    }
+}
+
+/// Halves a match's weight for every `half_life` days it was played before `now`. Matches
+/// without a time get full weight.
+pub fn recency_weight(half_life: f64, now: DateTime<Utc>) -> impl Fn(&Match) -> f64 {
+    move |m| match m.played_at(&Utc).or_else(|| m.scheduled_at(&Utc)) {
+        Some(t) => {
+            let days = (now - t).num_seconds().max(0) as f64 / 86_400.0;
+            0.5f64.powf(days / half_life)
+        },
+        None => 1.0,
+    }
+}
+
+/// The teams a season-wide ranking covers.
+pub enum Scope {
+    /// Every team that played an official event in the year.
+    Year(i32),
+    /// The teams in a district, by district key such as `2019fim`.
+    District(String),
+    /// The teams in a championship division.
+    Division(EventKey),
+}
+
+impl Scope {
+    pub fn year(&self) -> Result<i32, OprError> {
+        match self {
+            Scope::Year(year) => Ok(*year),
+            Scope::District(key) => key.get(..4).and_then(|y| y.parse().ok()).ok_or_else(|| OprError::UnknownDistrict(key.clone())),
+            Scope::Division(key) => Ok(key.year()),
+        }
+    }
+}
+
+/// Season-wide component OPRs, solved from every official event played by `time` that a team
+/// in `scope` attended, with each match weighted by `weight`. Teams outside the scope that
+/// played at those events are solved for too, but left out of the table. Standard errors
+/// cost a triangular solve per team, so they are only computed with `errors`.
+pub fn season_table(tba: &the_blue_alliance::TBA, scope: &Scope, time: DateTime<Utc>, participation: Participation, strength: Option<f64>, weight: &dyn Fn(&Match) -> f64, errors: bool) -> Result<ComponentTable, OprError> {
+    let year = scope.year()?;
+    let (events, teams) = match scope {
+        Scope::Year(_) => (Event::in_year(tba, year).wait().unwrap(), None),
+        Scope::District(key) => {
+            let district = the_blue_alliance::district::District::in_year(tba, year).wait().unwrap()
+                .into_iter()
+                .find(|d| &d.key == key)
+                .ok_or_else(|| OprError::UnknownDistrict(key.clone()))?;
+            let teams = district.team_keys(tba).wait().unwrap();
+            (team_events(tba, &teams, year), Some(teams))
+        },
+        Scope::Division(key) => {
+            let teams: Vec<TeamKey> = Event::from_key(tba, key).wait().unwrap()
+                .teams(tba).wait().unwrap()
+                .into_iter()
+                .map(|t| t.key)
+                .collect();
+            (team_events(tba, &teams, year), Some(teams))
+        },
+    };
+
+    let matches: Vec<Match> = futures::future::join_all(events.iter().filter(|e| e.is_official()).map(|e| e.matches(tba)))
+        .wait().unwrap()
+        .into_iter()
+        .flatten()
+        .filter(|m| state::played_before(m, time))
+        .collect();
+
+    let design = Design::for_weighted_matches(&matches, participation, strength, weight)?;
+    let mut table = ComponentTable::from_columns(columns(&design, &components(year), &Prior::EventMean)?, errors);
+    if let Some(teams) = teams {
+        let teams: HashSet<TeamKey> = teams.into_iter().collect();
+        table.values.retain(|t, _| teams.contains(t));
+    }
+    Ok(table)
+}
+
+/// Every event any of `teams` attended in `year`.
+fn team_events(tba: &the_blue_alliance::TBA, teams: &[TeamKey], year: i32) -> Vec<Event> {
+    let mut events: Vec<Event> = futures::future::join_all(teams.iter().map(|t| Event::for_team_key_in_year(tba, t, year)))
+        .wait().unwrap()
+        .into_iter()
+        .flatten()
+        .collect();
+    events.sort_unstable_by(|a, b| a.key.cmp(&b.key));
+    events.dedup_by(|a, b| a.key == b.key);
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use testing::qual;
+
+    /// Every way to split teams 1 to 6 into two alliances, scored exactly by `opr`.
+    fn round_robin(opr: &[f64; 6]) -> Vec<Match> {
+        let mut matches = Vec::new();
+        for a in 2..=6 {
+            for b in a + 1..=6 {
+                let red = [1, a, b];
+                let blue: Vec<u32> = (2..=6).filter(|t| *t != a && *t != b).collect();
+                let score = |teams: &[u32]| teams.iter().map(|&t| opr[t as usize - 1]).sum::<f64>() as i32;
+                matches.push(qual(matches.len() as u32 + 1, red, [blue[0], blue[1], blue[2]], (score(&red), score(&blue))));
+            }
+        }
+        matches
+    }
+
+    fn opr_metric(m: &Match, color: AllianceColor) -> Option<f64> {
+        m.score_for(color).map(f64::from)
+    }
+
+    #[test]
+    fn solves_a_determined_schedule_exactly() {
+        let truth = [10.0, 20.0, 30.0, 40.0, 50.0, 60.0];
+        let matches = round_robin(&truth);
+        let design = Design::new(&matches, Participation::default(), 0.0).unwrap();
+        let fit = design.fit(opr_metric).unwrap();
+        for (i, &opr) in truth.iter().enumerate() {
+            let estimate = fit.get(&TeamKey::from_number(i as u32 + 1)).unwrap();
+            assert!((estimate.value - opr).abs() < 1e-9, "team {} got {}", i + 1, estimate.value);
+        }
+        assert!((fit.stats.r_squared - 1.0).abs() < 1e-9);
+        assert_eq!(fit.stats.alliances, 20);
+    }
+
+    #[test]
+    fn ridge_shrinks_toward_the_event_mean() {
+        let truth = [10.0, 20.0, 30.0, 40.0, 50.0, 60.0];
+        let matches = round_robin(&truth);
+        let plain = Design::new(&matches, Participation::default(), 0.0).unwrap().fit(opr_metric).unwrap();
+        let ridge = Design::new(&matches, Participation::default(), 5.0).unwrap().fit(opr_metric).unwrap();
+        let best = TeamKey::from_number(6);
+        let worst = TeamKey::from_number(1);
+        assert!(ridge.get(&best).unwrap().value < plain.get(&best).unwrap().value);
+        assert!(ridge.get(&worst).unwrap().value > plain.get(&worst).unwrap().value);
+    }
+
+    #[test]
+    fn surrogates_and_disqualified_teams_play_in_oprs_by_default() {
+        let mut matches = vec![qual(1, [1, 2, 3], [4, 5, 6], (30, 60))];
+        {
+            let red = &mut matches[0].alliances.as_mut().unwrap().red;
+            red.surrogate_team_keys = Some(vec![TeamKey::from_number(1)]);
+            red.dq_team_keys = Some(vec![TeamKey::from_number(2)]);
+        }
+        let teams = |participation| Design::new(&matches, participation, 1.0).unwrap().teams().len();
+        assert_eq!(teams(Participation::default()), 6);
+        assert_eq!(teams(Participation { exclude_from_oprs: true, ..Participation::default() }), 4);
+        assert_eq!(teams(Participation { exclude_from_oprs: true, count_surrogates: true, ..Participation::default() }), 5);
+    }
+
+    #[test]
+    fn reports_an_under_determined_schedule() {
+        let matches = vec![qual(1, [1, 2, 3], [4, 5, 6], (30, 60))];
+        match Design::new(&matches, Participation::default(), 0.0) {
+            Err(OprError::RankDeficient { teams, rank }) => assert_eq!((teams, rank), (6, 2)),
+            _ => panic!("one match cannot determine six teams"),
+        }
+    }
+
+    #[test]
+    fn reports_a_singular_schedule_despite_rounding() {
+        // Three matches cannot separate eight teams, but with these weights rounding leaves a
+        // tiny positive pivot that a plain Cholesky factorization accepts.
+        let matches = vec![
+            qual(1, [1, 5, 7], [10, 8, 9], (50, 40)),
+            qual(2, [7, 3, 6], [8, 5, 1], (50, 40)),
+            qual(3, [1, 5, 9], [7, 3, 8], (50, 40)),
+        ];
+        let weight = |m: &Match| 1.0 / (3.0 + f64::from(m.match_number)).sqrt();
+        match Design::weighted(&matches, Participation::default(), 0.0, &weight) {
+            Err(OprError::RankDeficient { teams, rank }) => assert_eq!((teams, rank), (8, 6)),
+            Err(e) => panic!("unexpected error {}", e),
+            Ok(_) => panic!("three matches cannot determine eight teams"),
+        }
+    }
+
+    #[test]
+    fn falls_back_to_ridge_when_under_determined() {
+        let matches = vec![
+            qual(1, [1, 5, 7], [10, 8, 9], (50, 40)),
+            qual(2, [7, 3, 6], [8, 5, 1], (50, 40)),
+            qual(3, [1, 5, 9], [7, 3, 8], (50, 40)),
+        ];
+        let weight = |m: &Match| 1.0 / (3.0 + f64::from(m.match_number)).sqrt();
+        let design = Design::for_weighted_matches(&matches, Participation::default(), None, &weight).unwrap();
+        let fit = design.fit(opr_metric).unwrap();
+        let ridge = Design::weighted(&matches, Participation::default(), DEFAULT_STRENGTH, &weight).unwrap().fit(opr_metric).unwrap();
+        for team in design.teams() {
+            let value = fit.get(team).unwrap().value;
+            assert!(value.abs() < 50.0, "{} got {}", team, value);
+            assert!((value - ridge.get(team).unwrap().value).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn uses_plain_least_squares_when_determined() {
+        let truth = [10.0, 20.0, 30.0, 40.0, 50.0, 60.0];
+        let matches = round_robin(&truth);
+        let fit = Design::for_matches(&matches, Participation::default(), None).unwrap().fit(opr_metric).unwrap();
+        assert!((fit.get(&TeamKey::from_number(6)).unwrap().value - 60.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn standard_errors_match_the_dense_inverse() {
+        let mut matches = round_robin(&[10.0, 20.0, 30.0, 40.0, 50.0, 60.0]);
+        for (i, m) in matches.iter_mut().enumerate() {
+            let alliances = m.alliances.as_mut().unwrap();
+            alliances.red.score += (i as i32 * 7) % 5 - 2;
+            alliances.blue.score -= (i as i32 * 3) % 4 - 1;
+        }
+        for &strength in &[0.0, DEFAULT_STRENGTH] {
+            let design = Design::new(&matches, Participation::default(), strength).unwrap();
+            let fit = design.fit(opr_metric).unwrap();
+            let inverse = normal_matrix(6, design.rows.iter(), strength).try_inverse().unwrap();
+            for i in 0..6 {
+                let expected = (inverse[(i, i)] * fit.variance).sqrt();
+                assert!((fit.get(&design.teams()[i]).unwrap().standard_error - expected).abs() < 1e-9);
+            }
+            let expected = ((inverse[(0, 0)] + inverse[(3, 3)] - 2.0 * inverse[(0, 3)]) * fit.variance).sqrt();
+            let difference = fit.difference(&design.teams()[0], &design.teams()[3]).unwrap();
+            assert!((difference.standard_error - expected).abs() < 1e-9);
+            assert!((fit.factor.inverse_trace() - inverse.trace()).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn reports_no_matches() {
+        let matches = vec![qual(1, [1, 2, 3], [4, 5, 6], (-1, -1))];
+        assert_eq!(Design::new(&matches, Participation::default(), 0.0).err(), Some(OprError::NoMatches));
+    }
+}
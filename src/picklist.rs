@@ -0,0 +1,301 @@
+use std::collections::HashMap;
+use std::fmt;
+use the_blue_alliance::keys::TeamKey;
+use consistency::Contributions;
+use opr::ComponentTable;
+
+/// Columns that are not a part of the game, and so say nothing about what a team adds to an
+/// alliance beyond its OPR.
+const GENERIC: [&str; 6] = ["OPR", "DPR", "CCWM", "Auto", "Teleop", "Fouls"];
+
+/// How many points a standard deviation of shortfall in a component is worth, per point the
+/// candidate scores above average in it.
+const COMPLEMENT_WEIGHT: f64 = 1.0;
+
+/// How many points of OPR one point of standard deviation in a team's contributions from match
+/// to match costs.
+const CONSISTENCY_WEIGHT: f64 = 0.5;
+
+/// How many picks each alliance makes, in serpentine order.
+const ROUNDS: usize = 2;
+
+/// Why picks could not be recommended.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PicklistError {
+    /// The slot is not one of the event's alliances.
+    NoAlliance { slot: usize, alliances: usize },
+}
+
+impl fmt::Display for PicklistError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PicklistError::NoAlliance { slot, alliances } => write!(f, "There is no alliance {} at an event with {} alliances", slot, alliances),
+        }
+    }
+}
+
+/// How many alliances an event with `teams` teams has: up to 8, with enough teams for each.
+pub fn alliances_count(teams: usize) -> usize {
+    std::cmp::min(8, teams / (ROUNDS + 1))
+}
+
+/// A team's value to an alliance, and why.
+#[derive(Debug, Clone)]
+pub struct Pick {
+    pub team: TeamKey,
+    pub value: f64,
+    pub reasons: Vec<String>,
+}
+
+/// Recommended picks for one captain.
+pub struct Picklist {
+    /// Every team available for the captain's first pick, best first.
+    pub ranked: Vec<Pick>,
+    pub first: Option<Pick>,
+    pub second: Option<Pick>,
+    /// The teams other alliances are expected to take before each of the captain's picks,
+    /// with the seed that takes them.
+    pub taken_before_first: Vec<(usize, TeamKey)>,
+    pub taken_before_second: Vec<(usize, TeamKey)>,
+}
+
+/// Scores teams from component OPRs.
+struct Scorer<'t> {
+    oprs: &'t ComponentTable,
+    /// Game component columns, with their mean and standard deviation over every team.
+    components: Vec<(usize, f64, f64)>,
+    /// How much each team's OPR contribution varies from match to match.
+    spreads: HashMap<&'t TeamKey, f64>,
+}
+
+impl<'t> Scorer<'t> {
+    fn new(oprs: &'t ComponentTable, contributions: &'t [Contributions]) -> Scorer<'t> {
+        let components = oprs.columns.iter().enumerate()
+            .filter(|(_, name)| !GENERIC.contains(name))
+            .filter_map(|(i, _)| Some((i, oprs.column_mean(i)?, oprs.column_sd(i)?)))
+            .filter(|&(_, _, sd)| sd > 0.0)
+            .collect();
+        let spreads = contributions.iter()
+            .filter_map(|c| Some((&c.team, c.standard_deviation()?)))
+            .collect();
+        Scorer { oprs, components, spreads }
+    }
+
+    fn value(&self, team: &TeamKey, column: usize) -> f64 {
+        self.oprs.value_or_mean(team, column).unwrap_or(0.0)
+    }
+
+    /// What `candidate` adds to an alliance of `alliance`: its OPR, plus credit for being
+    /// strong where the alliance is weak, less a penalty for scoring unevenly.
+    fn pick(&self, alliance: &[&TeamKey], candidate: &TeamKey) -> Pick {
+        let mut reasons = Vec::new();
+        let opr = self.value(candidate, 0);
+        let mut value = opr;
+
+        let mut strengths: Vec<(f64, String)> = Vec::new();
+        for &(column, mean, sd) in &self.components {
+            let name = self.oprs.columns[column];
+            let candidate_value = self.value(candidate, column);
+            let z = (candidate_value - mean) / sd;
+            if z > 0.5 {
+                strengths.push((z, format!("strong {} ({:.1})", name, candidate_value)));
+            }
+
+            let shortfall = alliance.iter().map(|t| (mean - self.value(t, column)) / sd).sum::<f64>() / alliance.len() as f64;
+            if shortfall > 0.0 && candidate_value > mean {
+                let bonus = COMPLEMENT_WEIGHT * shortfall * (candidate_value - mean);
+                value += bonus;
+                reasons.push(format!("covers weak {} (+{:.1})", name, bonus));
+            }
+        }
+        strengths.sort_unstable_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
+        reasons.splice(0..0, strengths.into_iter().take(2).map(|(_, s)| s));
+
+        if let Some(&sd) = self.spreads.get(candidate) {
+            value -= CONSISTENCY_WEIGHT * sd;
+            reasons.push(format!("OPR {:.1}, SD {:.1} per match", opr, sd));
+        } else {
+            reasons.push(format!("OPR {:.1}", opr));
+        }
+
+        Pick {
+            team: candidate.clone(),
+            value,
+            reasons,
+        }
+    }
+
+    fn best<'a, I: Iterator<Item = &'a TeamKey>>(&self, alliance: &[&TeamKey], available: I) -> Option<Pick> {
+        available
+            .map(|t| self.pick(alliance, t))
+            .max_by(|a, b| a.value.partial_cmp(&b.value).unwrap_or(std::cmp::Ordering::Equal))
+    }
+}
+
+/// Recommends picks for the captain of alliance `slot` (starting at 1), assuming the other
+/// captains are the highest ranked teams left and each takes the best team for its own
+/// alliance. Declined invitations and backup robots are not modeled.
+///
+/// `ranking` is every team at the event, best first, and `contributions` each team's OPR
+/// contributions to the matches it played, which say how consistent it is.
+pub fn picklist(oprs: &ComponentTable, contributions: &[Contributions], ranking: &[TeamKey], captain: &TeamKey, slot: usize) -> Result<Picklist, PicklistError> {
+    let alliances_count = alliances_count(ranking.len());
+    if slot == 0 || slot > alliances_count {
+        return Err(PicklistError::NoAlliance { slot, alliances: alliances_count });
+    }
+
+    let scorer = Scorer::new(oprs, contributions);
+    let mut alliances: Vec<Vec<TeamKey>> = Vec::new();
+    let mut picked: Vec<TeamKey> = vec![captain.clone()];
+
+    let mut result = Picklist {
+        ranked: Vec::new(),
+        first: None,
+        second: None,
+        taken_before_first: Vec::new(),
+        taken_before_second: Vec::new(),
+    };
+
+    for round in 0..ROUNDS {
+        let order: Vec<usize> = if round % 2 == 0 {
+            (0..alliances_count).collect()
+        } else {
+            (0..alliances_count).rev().collect()
+        };
+
+        for seed in order {
+            if alliances.len() <= seed {
+                let next_captain = if seed + 1 == slot {
+                    captain.clone()
+                } else {
+                    match ranking.iter().find(|t| !picked.contains(t)) {
+                        Some(t) => t.clone(),
+                        None => break,
+                    }
+                };
+                if !picked.contains(&next_captain) {
+                    picked.push(next_captain.clone());
+                }
+                alliances.push(vec![next_captain]);
+            }
+
+            let alliance: Vec<&TeamKey> = alliances[seed].iter().collect();
+            let available = ranking.iter().filter(|t| !picked.contains(t));
+
+            if seed + 1 == slot {
+                if round == 0 {
+                    let mut ranked: Vec<Pick> = available.clone().map(|t| scorer.pick(&alliance, t)).collect();
+                    ranked.sort_unstable_by(|a, b| b.value.partial_cmp(&a.value).unwrap_or(std::cmp::Ordering::Equal));
+                    result.ranked = ranked;
+                    result.first = scorer.best(&alliance, available);
+                } else {
+                    result.second = scorer.best(&alliance, available);
+                }
+                let pick = if round == 0 { &result.first } else { &result.second };
+                if let Some(ref pick) = pick {
+                    picked.push(pick.team.clone());
+                    alliances[seed].push(pick.team.clone());
+                }
+            } else if let Some(pick) = scorer.best(&alliance, available) {
+                let taken = (seed + 1, pick.team.clone());
+                if result.first.is_none() {
+                    result.taken_before_first.push(taken);
+                } else if result.second.is_none() {
+                    result.taken_before_second.push(taken);
+                }
+                picked.push(pick.team.clone());
+                alliances[seed].push(pick.team);
+            }
+        }
+    }
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use testing;
+
+    fn team(n: u32) -> TeamKey {
+        TeamKey::from_number(n)
+    }
+
+    /// Nine teams, ranked by number, with OPRs that fall with rank.
+    fn event() -> (ComponentTable, Vec<TeamKey>) {
+        let rows: Vec<(u32, Vec<f64>)> = (1..=9).map(|n| (n, vec![f64::from(100 - n)])).collect();
+        let rows: Vec<(u32, &[f64])> = rows.iter().map(|(n, v)| (*n, &v[..])).collect();
+        (testing::oprs(vec!["OPR"], &rows), (1..=9).map(team).collect())
+    }
+
+    #[test]
+    fn picks_in_serpentine_order() {
+        let (oprs, ranking) = event();
+
+        // The first seed picks first in the first round and last in the second.
+        let list = picklist(&oprs, &[], &ranking, &team(1), 1).unwrap();
+        assert_eq!(list.first.unwrap().team, team(2));
+        assert_eq!(list.second.unwrap().team, team(9));
+        assert!(list.taken_before_first.is_empty());
+        assert_eq!(list.taken_before_second, vec![(2, team(4)), (3, team(6)), (3, team(7)), (2, team(8))]);
+
+        // The last seed picks twice in a row.
+        let list = picklist(&oprs, &[], &ranking, &team(3), 3).unwrap();
+        assert_eq!(list.first.unwrap().team, team(6));
+        assert_eq!(list.second.unwrap().team, team(7));
+        assert_eq!(list.taken_before_first, vec![(1, team(2)), (2, team(5))]);
+        assert!(list.taken_before_second.is_empty());
+        assert_eq!(list.ranked.iter().map(|p| p.team.clone()).collect::<Vec<_>>(), vec![team(6), team(7), team(8), team(9)]);
+    }
+
+    #[test]
+    fn other_captains_are_the_highest_ranked_left() {
+        let (oprs, ranking) = event();
+        // With team 5 captaining the first alliance it takes team 1, and teams 2 and 4 captain
+        // the other alliances.
+        let list = picklist(&oprs, &[], &ranking, &team(5), 1).unwrap();
+        assert_eq!(list.first.unwrap().team, team(1));
+        assert_eq!(list.taken_before_second[..2], [(2, team(3)), (3, team(6))]);
+    }
+
+    #[test]
+    fn rejects_slots_without_an_alliance() {
+        let (oprs, ranking) = event();
+        assert_eq!(alliances_count(ranking.len()), 3);
+        assert_eq!(picklist(&oprs, &[], &ranking, &team(1), 4).err(), Some(PicklistError::NoAlliance { slot: 4, alliances: 3 }));
+        assert_eq!(picklist(&oprs, &[], &ranking, &team(1), 0).err(), Some(PicklistError::NoAlliance { slot: 0, alliances: 3 }));
+        assert_eq!(alliances_count(40), 8);
+    }
+
+    #[test]
+    fn covers_the_alliances_weakness() {
+        // Team 1 is weak at cargo. Teams 2 and 3 have the same OPR, but team 2 scores it in cargo.
+        let oprs = testing::oprs(vec!["OPR", "Cargo", "Panel"], &[
+            (1, &[50.0, 5.0, 45.0]),
+            (2, &[40.0, 30.0, 10.0]),
+            (3, &[40.0, 10.0, 30.0]),
+            (4, &[30.0, 15.0, 15.0]),
+        ]);
+        let scorer = Scorer::new(&oprs, &[]);
+        let captain = team(1);
+        let cargo = scorer.pick(&[&captain], &team(2));
+        let panel = scorer.pick(&[&captain], &team(3));
+        assert!(cargo.value > 40.0);
+        assert_eq!(panel.value, 40.0);
+        assert!(cargo.reasons.iter().any(|r| r.starts_with("covers weak Cargo")));
+    }
+
+    #[test]
+    fn prefers_consistent_teams() {
+        let (oprs, _) = event();
+        let contributions = vec![
+            Contributions { team: team(5), values: vec![95.0, 95.0, 95.0] },
+            Contributions { team: team(6), values: vec![74.0, 114.0, 94.0] },
+        ];
+        let scorer = Scorer::new(&oprs, &contributions);
+        let captain = team(1);
+        assert_eq!(scorer.pick(&[&captain], &team(5)).value, 95.0);
+        // A standard deviation of 20 points per match costs 10 points of value.
+        assert!((scorer.pick(&[&captain], &team(6)).value - 84.0).abs() < 1e-9);
+    }
+}
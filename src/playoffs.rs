@@ -0,0 +1,295 @@
+use std::collections::HashMap;
+use std::fmt;
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use the_blue_alliance::event::Event;
+use the_blue_alliance::keys::TeamKey;
+use the_blue_alliance::matches::{AllianceColor, CompLevel, Match};
+use opr::ComponentTable;
+use predict;
+
+/// TBA's `playoff_type` for the eight alliance best-of-three bracket.
+const BRACKET_8_TEAM: i32 = 0;
+/// TBA's `playoff_type` for the double elimination bracket used since 2023.
+const DOUBLE_ELIM_8_TEAM: i32 = 10;
+
+/// Why a playoff could not be simulated.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PlayoffError {
+    /// TBA's `playoff_type` for a bracket with no layout here, such as the double elimination
+    /// bracket some off-season events used before 2023, which pairs alliances differently.
+    UnsupportedBracket(i32),
+    /// Only eight alliance brackets can be simulated.
+    AllianceCount(usize),
+    /// Zero runs were asked for, which gives no chances.
+    NoRuns,
+}
+
+impl fmt::Display for PlayoffError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PlayoffError::UnsupportedBracket(playoff_type) => write!(f, "Playoff type {} cannot be simulated", playoff_type),
+            PlayoffError::AllianceCount(count) => write!(f, "Only eight alliance brackets can be simulated, but there are {} alliances", count),
+            PlayoffError::NoRuns => write!(f, "At least one run is needed"),
+        }
+    }
+}
+
+/// How an eight alliance playoff bracket is played.
+///
+/// TBA files both brackets' matches under the existing comp levels: double elimination
+/// matches are semifinal sets `sf1` through `sf13` (see `Match::is_double_elimination`) and the
+/// finals are `f1`, so `CompLevel` does not need separate rounds for them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// Best-of-three quarterfinals, semifinals and finals, 1v8, 4v5, 2v7 and 3v6.
+    BestOfThree,
+    /// Single match upper and lower brackets, then best-of-three finals.
+    DoubleElimination,
+}
+
+impl Format {
+    /// The bracket an event plays, from its `playoff_type`, or from its year if TBA does not
+    /// say.
+    pub fn for_event(event: &Event) -> Result<Format, PlayoffError> {
+        match event.playoff_type {
+            Some(DOUBLE_ELIM_8_TEAM) => Ok(Format::DoubleElimination),
+            Some(BRACKET_8_TEAM) => Ok(Format::BestOfThree),
+            Some(playoff_type) => Err(PlayoffError::UnsupportedBracket(playoff_type)),
+            None if event.year >= 2023 => Ok(Format::DoubleElimination),
+            None => Ok(Format::BestOfThree),
+        }
+    }
+}
+
+/// Each alliance's chances of making the finals and winning the event.
+#[derive(Debug, Clone)]
+pub struct AllianceOutlook {
+    pub seed: usize,
+    pub strength: f64,
+    pub finalist: f64,
+    pub champion: f64,
+}
+
+/// One simulated playoff, with the results already played filled in.
+struct Bracket<'a, R: Rng> {
+    /// `win[a][b]` is the probability alliance `a` beats alliance `b` in one match.
+    win: &'a [Vec<f64>],
+    /// The alliance that won each match played so far, by comp level, set and match number.
+    played: &'a HashMap<(CompLevel, i32, i32), usize>,
+    rng: &'a mut R,
+}
+
+impl<'a, R: Rng> Bracket<'a, R> {
+    fn game(&mut self, level: CompLevel, set: i32, number: i32, a: usize, b: usize) -> usize {
+        match self.played.get(&(level, set, number)) {
+            Some(&winner) if winner == a || winner == b => winner,
+            _ => if self.rng.gen::<f64>() < self.win[a][b] { a } else { b },
+        }
+    }
+
+    /// Plays a best-of-three series, counting matches already played. Ties are replayed, so
+    /// only wins count.
+    fn series(&mut self, level: CompLevel, set: i32, a: usize, b: usize) -> usize {
+        let (mut a_wins, mut b_wins) = (0, 0);
+        let mut number = 1;
+        while a_wins < 2 && b_wins < 2 {
+            if self.game(level, set, number, a, b) == a {
+                a_wins += 1;
+            } else {
+                b_wins += 1;
+            }
+            number += 1;
+        }
+        if a_wins == 2 { a } else { b }
+    }
+
+    /// Returns the finalists, then the champion.
+    fn best_of_three(&mut self) -> ([usize; 2], usize) {
+        let qf = [
+            self.series(CompLevel::QuarterFinal, 1, 0, 7),
+            self.series(CompLevel::QuarterFinal, 2, 3, 4),
+            self.series(CompLevel::QuarterFinal, 3, 1, 6),
+            self.series(CompLevel::QuarterFinal, 4, 2, 5),
+        ];
+        let finalists = [
+            self.series(CompLevel::SemiFinal, 1, qf[0], qf[1]),
+            self.series(CompLevel::SemiFinal, 2, qf[2], qf[3]),
+        ];
+        (finalists, self.series(CompLevel::Final, 1, finalists[0], finalists[1]))
+    }
+
+    /// Plays bracket match `set` and returns the winner and loser.
+    fn bracket_match(&mut self, set: i32, a: usize, b: usize) -> (usize, usize) {
+        let winner = self.game(CompLevel::SemiFinal, set, 1, a, b);
+        (winner, if winner == a { b } else { a })
+    }
+
+    /// Returns the finalists, then the champion.
+    fn double_elimination(&mut self) -> ([usize; 2], usize) {
+        let (w1, l1) = self.bracket_match(1, 0, 7);
+        let (w2, l2) = self.bracket_match(2, 3, 4);
+        let (w3, l3) = self.bracket_match(3, 1, 6);
+        let (w4, l4) = self.bracket_match(4, 2, 5);
+        let (w5, _) = self.bracket_match(5, l1, l2);
+        let (w6, _) = self.bracket_match(6, l3, l4);
+        let (w7, l7) = self.bracket_match(7, w1, w2);
+        let (w8, l8) = self.bracket_match(8, w3, w4);
+        let (w9, _) = self.bracket_match(9, l7, w6);
+        let (w10, _) = self.bracket_match(10, l8, w5);
+        let (w11, l11) = self.bracket_match(11, w7, w8);
+        let (w12, _) = self.bracket_match(12, w10, w9);
+        let (w13, _) = self.bracket_match(13, l11, w12);
+        let finalists = [w11, w13];
+        (finalists, self.series(CompLevel::Final, 1, w11, w13))
+    }
+}
+
+/// An alliance's expected score: the sum of its three best OPRs, since only three robots play.
+fn alliance_strength(oprs: &ComponentTable, teams: &[TeamKey]) -> f64 {
+    let mut values: Vec<f64> = teams.iter().filter_map(|t| oprs.value_or_mean(t, 0)).collect();
+    values.sort_unstable_by(|a, b| b.partial_cmp(a).unwrap_or(std::cmp::Ordering::Equal));
+    values.iter().take(3).sum()
+}
+
+/// Which alliance played on one side of a match, by the teams on it.
+fn alliance_index(alliances: &[Vec<TeamKey>], m: &Match, color: AllianceColor) -> Option<usize> {
+    let teams = &m.alliances.as_ref()?.get(color).team_keys;
+    alliances.iter().position(|a| teams.iter().any(|t| a.contains(t)))
+}
+
+/// Simulates the playoffs `runs` times between `alliances`, given in seed order, with each
+/// alliance's score drawn around its OPR sum. Playoff matches in `played` that have a result
+/// are kept as they were. The same seed always gives the same results.
+pub fn simulate(format: Format, alliances: &[Vec<TeamKey>], oprs: &ComponentTable, played: &[Match], runs: usize, seed: u64) -> Result<Vec<AllianceOutlook>, PlayoffError> {
+    if alliances.len() != 8 {
+        return Err(PlayoffError::AllianceCount(alliances.len()));
+    }
+    if runs == 0 {
+        return Err(PlayoffError::NoRuns);
+    }
+
+    let strengths: Vec<f64> = alliances.iter().map(|a| alliance_strength(oprs, a)).collect();
+    let margin_sd = oprs.stats[0].map_or(f64::NAN, |s| s.residual_sd) * std::f64::consts::SQRT_2;
+    let win: Vec<Vec<f64>> = strengths.iter()
+        .map(|a| strengths.iter().map(|b| predict::margin_win_probability(a - b, margin_sd)).collect())
+        .collect();
+
+    let results: HashMap<(CompLevel, i32, i32), usize> = played.iter()
+        .filter(|m| m.comp_level != CompLevel::QualificationMatch)
+        .filter_map(|m| {
+            let winner = m.winning_alliance?.color()?;
+            Some(((m.comp_level, m.set_number, m.match_number), alliance_index(alliances, m, winner)?))
+        })
+        .collect();
+
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut finalist = vec![0usize; alliances.len()];
+    let mut champion = vec![0usize; alliances.len()];
+    for _ in 0..runs {
+        let mut bracket = Bracket {
+            win: &win,
+            played: &results,
+            rng: &mut rng,
+        };
+        let (finalists, winner) = match format {
+            Format::BestOfThree => bracket.best_of_three(),
+            Format::DoubleElimination => bracket.double_elimination(),
+        };
+        for &f in &finalists {
+            finalist[f] += 1;
+        }
+        champion[winner] += 1;
+    }
+
+    Ok((0..alliances.len())
+        .map(|i| AllianceOutlook {
+            seed: i + 1,
+            strength: strengths[i],
+            finalist: finalist[i] as f64 / runs as f64,
+            champion: champion[i] as f64 / runs as f64,
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opr::{Estimate, FitStats};
+
+    /// Every alliance beats every lower seeded one.
+    fn seeds_win() -> Vec<Vec<f64>> {
+        (0..8).map(|a| (0..8).map(|b| if a < b { 1.0 } else { 0.0 }).collect()).collect()
+    }
+
+    fn play(format: Format, played: &HashMap<(CompLevel, i32, i32), usize>) -> ([usize; 2], usize) {
+        let win = seeds_win();
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut bracket = Bracket { win: &win, played, rng: &mut rng };
+        match format {
+            Format::BestOfThree => bracket.best_of_three(),
+            Format::DoubleElimination => bracket.double_elimination(),
+        }
+    }
+
+    #[test]
+    fn top_seeds_meet_in_the_finals() {
+        for &format in &[Format::BestOfThree, Format::DoubleElimination] {
+            assert_eq!(play(format, &HashMap::new()), ([0, 1], 0));
+        }
+    }
+
+    #[test]
+    fn played_results_are_kept() {
+        // The 8 seed upsets the 1 seed in the first round.
+        let mut played = HashMap::new();
+        played.insert((CompLevel::QuarterFinal, 1, 1), 7);
+        played.insert((CompLevel::QuarterFinal, 1, 2), 7);
+        assert_eq!(play(Format::BestOfThree, &played), ([3, 1], 1));
+
+        // In double elimination the 1 seed drops to the lower bracket and can come back
+        // through it to the finals.
+        let mut played = HashMap::new();
+        played.insert((CompLevel::SemiFinal, 1, 1), 7);
+        assert_eq!(play(Format::DoubleElimination, &played), ([1, 0], 0));
+    }
+
+    fn table_with_spread(residual_sd: f64) -> ComponentTable {
+        ComponentTable {
+            columns: vec!["OPR"],
+            values: (1..=24).map(|n| (TeamKey::from_number(n), vec![Estimate { value: f64::from(100 - n), standard_error: 1.0 }])).collect(),
+            stats: vec![Some(FitStats { r_squared: 0.5, rmse: 10.0, residual_sd, alliances: 100 })],
+        }
+    }
+
+    fn table() -> ComponentTable {
+        table_with_spread(10.0)
+    }
+
+    fn alliances(count: u32) -> Vec<Vec<TeamKey>> {
+        (0..count).map(|a| (1..=3).map(|i| TeamKey::from_number(a * 3 + i)).collect()).collect()
+    }
+
+    #[test]
+    fn chances_add_up() {
+        let outlooks = simulate(Format::DoubleElimination, &alliances(8), &table(), &[], 1000, 1).unwrap();
+        assert!((outlooks.iter().map(|o| o.champion).sum::<f64>() - 1.0).abs() < 1e-9);
+        assert!((outlooks.iter().map(|o| o.finalist).sum::<f64>() - 2.0).abs() < 1e-9);
+        assert!(outlooks[0].champion > outlooks[7].champion);
+    }
+
+    #[test]
+    fn unknown_spread_leaves_series_open() {
+        // A fit with no residual degrees of freedom has a NaN spread, which must not make the
+        // strongest alliance win every series.
+        let outlooks = simulate(Format::BestOfThree, &alliances(8), &table_with_spread(f64::NAN), &[], 1000, 1).unwrap();
+        assert!(outlooks[0].champion < 0.5);
+        assert!(outlooks[7].champion > 0.0);
+    }
+
+    #[test]
+    fn rejects_other_brackets() {
+        assert_eq!(simulate(Format::BestOfThree, &alliances(4), &table(), &[], 10, 1).err(), Some(PlayoffError::AllianceCount(4)));
+        assert_eq!(simulate(Format::BestOfThree, &alliances(8), &table(), &[], 0, 1).err(), Some(PlayoffError::NoRuns));
+    }
+}
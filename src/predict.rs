@@ -0,0 +1,221 @@
+use std::fmt;
+use std::str::FromStr;
+use the_blue_alliance::keys::TeamKey;
+use the_blue_alliance::matches::{AllianceColor, CompLevel, Match};
+use elo::Elo;
+use opr::{self, ComponentTable, Design, Prior};
+use state::Participation;
+
+/// What a prediction is based on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Model {
+    /// Sums of the alliances' OPRs, with the spread of the OPR fit's residuals.
+    Opr,
+    /// Sums of the alliances' auto, teleop and foul OPRs, each with its own spread.
+    Components,
+    /// Elo ratings, which predict the winner but not the score.
+    Elo,
+}
+
+impl Model {
+    pub const ALL: [Model; 3] = [Model::Opr, Model::Components, Model::Elo];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Model::Opr => "opr",
+            Model::Components => "components",
+            Model::Elo => "elo",
+        }
+    }
+}
+
+impl fmt::Display for Model {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Model {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Model, String> {
+        Model::ALL.iter().cloned()
+            .find(|m| m.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| format!("\"{}\" is not a prediction model", s))
+    }
+}
+
+/// The chance each alliance earns a ranking point bonus.
+#[derive(Debug, Clone)]
+pub struct Bonus {
+    pub name: &'static str,
+    pub red: f64,
+    pub blue: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct Prediction {
+    /// Predicted red and blue scores, if the model predicts scores.
+    pub scores: Option<(f64, f64)>,
+    /// The probability that red wins, with a tie counting as half a win.
+    pub red_win: f64,
+    /// Ranking point bonuses, for qualification matches in seasons that have them.
+    pub bonuses: Vec<Bonus>,
+}
+
+impl Prediction {
+    pub fn score(&self, color: AllianceColor) -> Option<f64> {
+        self.scores.map(|(red, blue)| match color {
+            AllianceColor::Red => red,
+            AllianceColor::Blue => blue,
+        })
+    }
+}
+
+/// The standard normal cumulative distribution function, accurate to about 1e-7.
+pub fn normal_cdf(x: f64) -> f64 {
+    // Abramowitz and Stegun 7.1.26.
+    let z = x.abs() / std::f64::consts::SQRT_2;
+    let t = 1.0 / (1.0 + 0.327_591_1 * z);
+    let poly = t * (0.254_829_592 + t * (-0.284_496_736 + t * (1.421_413_741 + t * (-1.453_152_027 + t * 1.061_405_429))));
+    let erf = 1.0 - poly * (-z * z).exp();
+    if x >= 0.0 {
+        0.5 * (1.0 + erf)
+    } else {
+        0.5 * (1.0 - erf)
+    }
+}
+
+/// The probability that a normally distributed margin is positive. A spread that is not a
+/// positive number, like the NaN of a fit with no residual degrees of freedom, says nothing
+/// about the margin, so either side is as likely to win.
+pub fn margin_win_probability(margin: f64, sd: f64) -> f64 {
+    if sd.is_finite() && sd > 0.0 {
+        normal_cdf(margin / sd)
+    } else {
+        0.5
+    }
+}
+
+fn alliance_sum(table: &ComponentTable, column: usize, teams: &[TeamKey]) -> Option<f64> {
+    teams.iter().map(|t| table.value_or_mean(t, column)).sum()
+}
+
+/// Predicts matches from the matches played before them.
+pub struct Predictor {
+    oprs: Option<ComponentTable>,
+    bonuses: Option<ComponentTable>,
+    elo: Option<Elo>,
+}
+
+impl Predictor {
+    /// Builds the OPR and component models from played `matches`. Ranking point bonuses are
+    /// only solved from qualification matches, where they are awarded. Elo predictions need
+    /// ratings that already include `matches`.
+    pub fn new(year: i32, matches: &[Match], participation: Participation, strength: Option<f64>, elo: Option<Elo>) -> Predictor {
+        let oprs = Design::for_matches(matches, participation, strength)
+            .and_then(|design| opr::component_table(&design, &opr::components(year), &Prior::EventMean))
+            .ok();
+
+        let quals: Vec<Match> = matches.iter()
+            .filter(|m| m.comp_level == CompLevel::QualificationMatch)
+            .cloned()
+            .collect();
+        let bonuses = Design::for_matches(&quals, participation, strength)
+            .and_then(|design| opr::component_table(&design, &opr::bonuses(year), &Prior::EventMean))
+            .ok();
+
+        Predictor {
+            oprs,
+            bonuses,
+            elo,
+        }
+    }
+
+    /// Component OPRs from the played matches, if there were enough to solve them.
+    pub fn oprs(&self) -> Option<&ComponentTable> {
+        self.oprs.as_ref()
+    }
+
+    /// Each team's share of the ranking point bonuses, if there were enough qualification
+    /// matches to solve them.
+    pub fn bonus_oprs(&self) -> Option<&ComponentTable> {
+        self.bonuses.as_ref()
+    }
+
+    /// Predicts a match, or `None` if the model has nothing to go on yet.
+    pub fn predict(&self, m: &Match, model: Model) -> Option<Prediction> {
+        let alliances = m.alliances.as_ref()?;
+        let red = &alliances.red.team_keys;
+        let blue = &alliances.blue.team_keys;
+
+        let (scores, red_win) = match model {
+            Model::Opr => {
+                let oprs = self.oprs.as_ref()?;
+                let sd = oprs.stats[0]?.residual_sd;
+                let scores = (alliance_sum(oprs, 0, red)?, alliance_sum(oprs, 0, blue)?);
+                (Some(scores), margin_win_probability(scores.0 - scores.1, sd * std::f64::consts::SQRT_2))
+            },
+            Model::Components => {
+                let oprs = self.oprs.as_ref()?;
+                let columns: Vec<usize> = ["Auto", "Teleop", "Fouls"].iter().filter_map(|c| oprs.column(c)).collect();
+                let sum = |teams: &[TeamKey]| columns.iter().map(|&c| alliance_sum(oprs, c, teams)).sum::<Option<f64>>();
+                let scores = (sum(red)?, sum(blue)?);
+                let variance: f64 = columns.iter().filter_map(|&c| oprs.stats[c]).map(|s| s.residual_sd * s.residual_sd).sum();
+                (Some(scores), margin_win_probability(scores.0 - scores.1, (2.0 * variance).sqrt()))
+            },
+            Model::Elo => (None, self.elo.as_ref()?.win_probability(m, AllianceColor::Red)?),
+        };
+
+        let bonuses = match self.bonuses {
+            Some(ref bonuses) if m.comp_level == CompLevel::QualificationMatch => bonuses.columns.iter().enumerate()
+                .filter_map(|(i, &name)| Some(Bonus {
+                    name,
+                    red: alliance_sum(bonuses, i, red)?.clamp(0.0, 1.0),
+                    blue: alliance_sum(bonuses, i, blue)?.clamp(0.0, 1.0),
+                }))
+                .collect(),
+            _ => Vec::new(),
+        };
+
+        Some(Prediction {
+            scores,
+            red_win,
+            bonuses,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normal_cdf_matches_tables() {
+        let table = [(0.0, 0.5), (1.0, 0.841_344_746), (-1.0, 0.158_655_254), (opr::Z_95, 0.975), (-3.0, 0.001_349_898)];
+        for &(x, expected) in &table {
+            assert!((normal_cdf(x) - expected).abs() < 1e-7, "Φ({}) = {}", x, normal_cdf(x));
+        }
+    }
+
+    #[test]
+    fn normal_cdf_is_symmetric() {
+        for &x in &[0.3, 1.7, 4.2] {
+            assert!((normal_cdf(x) + normal_cdf(-x) - 1.0).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn margins_scale_by_spread() {
+        assert!((margin_win_probability(10.0, 10.0) - normal_cdf(1.0)).abs() < 1e-12);
+        assert!((margin_win_probability(-10.0, 20.0) - normal_cdf(-0.5)).abs() < 1e-12);
+    }
+
+    #[test]
+    fn unknown_spread_is_a_coin_flip() {
+        for &sd in &[0.0, -1.0, f64::NAN, f64::INFINITY] {
+            assert_eq!(margin_win_probability(5.0, sd), 0.5);
+            assert_eq!(margin_win_probability(-5.0, sd), 0.5);
+        }
+    }
+}
@@ -0,0 +1,349 @@
+use std::fmt;
+use chrono::{DateTime, Datelike, TimeZone, Utc};
+use futures::Future;
+use the_blue_alliance::event::{AllianceStatus, EliminationAlliance, Event};
+use the_blue_alliance::keys::TeamKey;
+use the_blue_alliance::team::Team;
+use consistency;
+use elo::{self, Elo, EloConfig};
+use history::Record;
+use opr::{self, Prior};
+use state::{self, EventState, Participation};
+
+/// Characters for a sparkline, from lowest to highest.
+const SPARKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// How a team did at one event.
+pub struct EventReport {
+    pub event: Event,
+    pub rank: Option<usize>,
+    /// Every match the team played, qualifications and playoffs.
+    pub record: Record,
+    pub opr: Option<f64>,
+    pub ccwm: Option<f64>,
+    pub playoffs: Option<Finish>,
+    pub awards: Vec<String>,
+}
+
+/// How a team did at each event it attended in a season, in the order they started.
+pub struct SeasonReport {
+    pub events: Vec<EventReport>,
+}
+
+impl SeasonReport {
+    /// The team's OPR at each event it has one for.
+    pub fn oprs(&self) -> Vec<f64> {
+        self.events.iter().filter_map(|e| e.opr).collect()
+    }
+
+    /// How much the team's OPR changed per event over the season, by least squares.
+    pub fn opr_trend(&self) -> Option<f64> {
+        consistency::slope(&self.oprs())
+    }
+}
+
+/// Draws `values` as a line of block characters scaled between their minimum and maximum.
+pub fn sparkline(values: &[f64]) -> String {
+    let min = values.iter().cloned().fold(f64::INFINITY, f64::min);
+    let max = values.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+    sparkline_between(values, min, max)
+}
+
+/// Draws `values` as a line of block characters, with `low` the lowest block and `high` the
+/// highest.
+pub fn sparkline_between(values: &[f64], low: f64, high: f64) -> String {
+    values.iter()
+        .map(|v| if high > low {
+            SPARKS[(((v - low) / (high - low)).clamp(0.0, 1.0) * (SPARKS.len() - 1) as f64).round() as usize]
+        } else {
+            SPARKS[SPARKS.len() / 2]
+        })
+        .collect()
+}
+
+/// Draws a team's ranks as a sparkline, with first place at the top and last at the bottom.
+pub fn rank_sparkline(ranks: &[usize], teams: usize) -> String {
+    let values: Vec<f64> = ranks.iter().map(|&r| -(r as f64)).collect();
+    sparkline_between(&values, -(teams as f64), -1.0)
+}
+
+/// How far an alliance made it in the playoffs, from worst to best. An alliance still playing
+/// is ahead of one knocked out at an unknown level, but behind any round it has yet to reach.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Stage {
+    NotPicked,
+    /// On an alliance that has not played yet.
+    Picked,
+    /// Knocked out at a level the status does not name.
+    Eliminated,
+    Playing,
+    Octofinals,
+    Quarterfinals,
+    /// Knocked out in this round of a double elimination bracket.
+    Round(u32),
+    Semifinals,
+    Finalist,
+    Winner,
+}
+
+impl Stage {
+    pub fn from_status(status: &AllianceStatus) -> Stage {
+        let round = status.double_elim_round.as_ref()
+            .and_then(|r| r.trim_start_matches("Round ").parse().ok());
+        match (status.status.as_deref(), status.level.as_deref(), round) {
+            (Some("won"), _, _) => Stage::Winner,
+            (Some("playing"), _, _) => Stage::Playing,
+            (_, Some("f"), _) => Stage::Finalist,
+            (_, _, Some(round)) => Stage::Round(round),
+            (_, Some("sf"), _) => Stage::Semifinals,
+            (_, Some("qf"), _) => Stage::Quarterfinals,
+            (_, Some("ef"), _) => Stage::Octofinals,
+            _ => Stage::Eliminated,
+        }
+    }
+}
+
+impl fmt::Display for Stage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Stage::NotPicked => f.write_str("Not picked"),
+            Stage::Picked => f.write_str("Picked"),
+            Stage::Eliminated => f.write_str("Eliminated"),
+            Stage::Playing => f.write_str("Playing"),
+            Stage::Octofinals => f.write_str("Octofinalist"),
+            Stage::Quarterfinals => f.write_str("Quarterfinalist"),
+            Stage::Round(round) => write!(f, "Out in round {}", round),
+            Stage::Semifinals => f.write_str("Semifinalist"),
+            Stage::Finalist => f.write_str("Finalist"),
+            Stage::Winner => f.write_str("Winner"),
+        }
+    }
+}
+
+/// How far a team made it in the playoffs at one event, and on which alliance.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Finish {
+    pub stage: Stage,
+    pub alliance: Option<usize>,
+}
+
+impl fmt::Display for Finish {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.alliance {
+            Some(alliance) => write!(f, "{} (alliance {})", self.stage, alliance),
+            None => write!(f, "{}", self.stage),
+        }
+    }
+}
+
+/// How far `team` made it in the playoffs, or `None` if alliances have not been picked.
+fn playoff_finish(alliances: Option<&[EliminationAlliance]>, team: &TeamKey) -> Option<Finish> {
+    let alliances = alliances?;
+    Some(match alliances.iter().enumerate().find(|(_, a)| a.teams().contains(&team)) {
+        Some((seed, alliance)) => Finish {
+            stage: alliance.status.as_ref().map(Stage::from_status).unwrap_or(Stage::Picked),
+            alliance: Some(seed + 1),
+        },
+        None => Finish {
+            stage: Stage::NotPicked,
+            alliance: None,
+        },
+    })
+}
+
+/// Reports on every event `team` attended in `year` that started before `time`. Playoff finishes
+/// and awards are as TBA has them now.
+pub fn season_report(tba: &the_blue_alliance::TBA, team: &Team, year: i32, time: DateTime<Utc>, participation: Participation, strength: Option<f64>) -> SeasonReport {
+    let mut events: Vec<Event> = team.events_in_year(tba, year).wait().unwrap()
+        .into_iter()
+        .filter(|e| Utc.from_utc_date(&e.start_date).and_hms(0, 0, 0) < time)
+        .collect();
+    events.sort_unstable();
+    let awards = team.awards_in_year(tba, year).wait().unwrap();
+
+    let components: Vec<opr::Component> = opr::components(year).into_iter()
+        .filter(|c| c.name == "OPR" || c.name == "CCWM")
+        .collect();
+
+    let reports = events.into_iter()
+        .map(|event| {
+            debug!("Reporting on {}", event.key);
+            let mut matches = event.matches(tba).wait().unwrap();
+            matches.retain(|m| state::played_before(m, time));
+
+            let mut record = Record::default();
+            for m in &matches {
+                let color = match m.alliance_of(&team.key) {
+                    Some(color) => color,
+                    None => continue,
+                };
+                if !participation.is_counted(m.alliances.as_ref().unwrap().get(color), &team.key) {
+                    continue;
+                }
+                if let Some(red) = elo::red_outcome(m) {
+                    record.add(if color == the_blue_alliance::matches::AllianceColor::Red { red } else { 1.0 - red });
+                }
+            }
+
+            let oprs = opr::Design::for_matches(&matches, participation, strength)
+                .and_then(|design| opr::component_table(&design, &components, &Prior::EventMean))
+                .ok();
+            let value = |column: usize| oprs.as_ref()
+                .and_then(|o| o.values.get(&team.key))
+                .map(|row| row[column].value)
+                .filter(|v| !v.is_nan());
+
+            let rank = EventState::new(&event, tba, time, participation).rank_of_team(&team.key);
+            let alliances = event.alliances(tba).wait().unwrap();
+
+            EventReport {
+                rank,
+                record,
+                opr: value(0),
+                ccwm: value(1),
+                playoffs: playoff_finish(alliances.as_deref(), &team.key),
+                awards: awards.iter().filter(|a| a.event_key == event.key).map(|a| a.name.clone()).collect(),
+                event,
+            }
+        })
+        .collect();
+
+    SeasonReport {
+        events: reports,
+    }
+}
+
+/// A summary of one season for a team.
+pub struct YearSummary {
+    pub year: i32,
+    pub events: usize,
+    pub best_finish: Option<Finish>,
+    /// The average share of teams a team ranked above at its events, from 0 for last to 100 for first.
+    pub rank_percentile: Option<f64>,
+    /// The team's Elo rating at the end of the season, or now for the current season.
+    pub elo: Option<f64>,
+    pub awards: Vec<String>,
+}
+
+/// Summarizes every season `team` has played, up to `time`, using TBA's rankings and playoff
+/// results. With `rate`, Elo ratings are computed from every season since the team's first, which
+/// needs every match of every one of those seasons.
+pub fn team_history(tba: &the_blue_alliance::TBA, team: &Team, time: DateTime<Utc>, participation: Participation, rate: bool) -> Vec<YearSummary> {
+    let mut years: Vec<i32> = team.years_participated(tba).wait().unwrap()
+        .into_iter()
+        .map(|y| y as i32)
+        .filter(|&y| y <= time.year())
+        .collect();
+    years.sort_unstable();
+
+    let mut elo = Elo::new(EloConfig::default(), participation);
+    let mut ratings = Vec::new();
+    if rate {
+        if let (Some(&first), Some(&last)) = (years.first(), years.last()) {
+            for year in first..=last {
+                debug!("Rating {}", year);
+                elo.add_season(tba, year, time, None);
+                ratings.push((year, elo.rating(&team.key)));
+            }
+        }
+    }
+
+    years.iter()
+        .map(|&year| {
+            let statuses = team.event_statuses_in_year(tba, year).wait().unwrap();
+            let statuses: Vec<_> = statuses.values().filter_map(|s| s.as_ref()).collect();
+
+            let percentiles: Vec<f64> = statuses.iter()
+                .filter_map(|s| {
+                    let qual = s.qual.as_ref()?;
+                    let rank = qual.ranking.as_ref()?.rank?;
+                    let teams = qual.num_teams.filter(|&n| n > 1)?;
+                    Some(100.0 * f64::from(teams - rank) / f64::from(teams - 1))
+                })
+                .collect();
+            let best_finish = statuses.iter()
+                .filter_map(|s| match s.alliance {
+                    Some(ref alliance) => Some(Finish {
+                        stage: s.playoff.as_ref().map(Stage::from_status).unwrap_or(Stage::Picked),
+                        alliance: Some(alliance.number as usize),
+                    }),
+                    None if s.qual.is_some() => Some(Finish { stage: Stage::NotPicked, alliance: None }),
+                    None => None,
+                })
+                .max();
+
+            YearSummary {
+                year,
+                events: statuses.len(),
+                best_finish,
+                rank_percentile: if percentiles.is_empty() {
+                    None
+                } else {
+                    Some(percentiles.iter().sum::<f64>() / percentiles.len() as f64)
+                },
+                elo: ratings.iter().find(|&&(y, _)| y == year).map(|&(_, r)| r),
+                awards: team.awards_in_year(tba, year).wait().unwrap()
+                    .into_iter()
+                    .map(|a| a.name)
+                    .collect(),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status(status: &str, level: &str, round: Option<&str>) -> AllianceStatus {
+        AllianceStatus {
+            level: Some(level.to_owned()),
+            status: Some(status.to_owned()),
+            double_elim_round: round.map(str::to_owned),
+        }
+    }
+
+    #[test]
+    fn sparklines_scale_between_the_extremes() {
+        assert_eq!(sparkline(&[]), "");
+        assert_eq!(sparkline(&[1.0, 8.0, 4.5]), "▁█▅");
+        assert_eq!(sparkline(&[3.0, 3.0, 3.0]), "▅▅▅");
+    }
+
+    #[test]
+    fn first_place_is_the_top_of_a_rank_sparkline() {
+        assert_eq!(rank_sparkline(&[8, 1, 8], 8), "▁█▁");
+        assert_eq!(rank_sparkline(&[1, 1], 1), "▅▅");
+        assert_eq!(rank_sparkline(&[], 30), "");
+    }
+
+    #[test]
+    fn stages_come_from_the_last_series() {
+        assert_eq!(Stage::from_status(&status("won", "f", Some("Finals"))), Stage::Winner);
+        assert_eq!(Stage::from_status(&status("playing", "sf", Some("Round 2"))), Stage::Playing);
+        assert_eq!(Stage::from_status(&status("eliminated", "f", Some("Finals"))), Stage::Finalist);
+        assert_eq!(Stage::from_status(&status("eliminated", "qf", None)), Stage::Quarterfinals);
+        assert_eq!(Stage::from_status(&status("eliminated", "ef", None)), Stage::Octofinals);
+        assert_eq!(Stage::from_status(&status("eliminated", "", None)), Stage::Eliminated);
+    }
+
+    #[test]
+    fn double_elimination_rounds_are_parsed() {
+        assert_eq!(Stage::from_status(&status("eliminated", "sf", Some("Round 3"))), Stage::Round(3));
+        assert_eq!(Stage::from_status(&status("eliminated", "sf", Some("Round 5"))), Stage::Round(5));
+        assert_eq!(Stage::from_status(&status("eliminated", "sf", Some("Finals"))), Stage::Semifinals);
+    }
+
+    #[test]
+    fn still_playing_beats_an_unknown_elimination() {
+        let finish = |stage| Finish { stage, alliance: Some(1) };
+        let best = vec![finish(Stage::Eliminated), finish(Stage::Playing), finish(Stage::Picked)]
+            .into_iter()
+            .max();
+        assert_eq!(best, Some(finish(Stage::Playing)));
+        assert!(Stage::Playing < Stage::Octofinals);
+        assert!(Stage::Quarterfinals < Stage::Round(1));
+        assert!(Stage::Round(4) < Stage::Round(5));
+        assert!(Stage::Round(5) < Stage::Semifinals);
+    }
+}
@@ -0,0 +1,184 @@
+use std::collections::HashMap;
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use rand::seq::index::sample;
+use the_blue_alliance::keys::TeamKey;
+use the_blue_alliance::matches::{AllianceColor, CompLevel, Match};
+use opr::ComponentTable;
+use predict;
+use season::Season;
+use state::Participation;
+
+/// How many random schedules the baseline averages over when the caller has no preference.
+pub const DEFAULT_RUNS: usize = 1000;
+
+/// How a team's qualification schedule compares to a random one.
+#[derive(Debug, Clone)]
+pub struct TeamSchedule {
+    pub team: TeamKey,
+    pub matches: usize,
+    /// The average summed OPR of the team's partners in a match.
+    pub partner_strength: f64,
+    /// The average summed OPR of the team's opponents in a match.
+    pub opponent_strength: f64,
+    /// Ranking points the team is expected to earn over its real schedule.
+    pub expected_ranking_points: f64,
+    /// Ranking points the team is expected to earn over the same number of matches with
+    /// partners and opponents drawn at random from the event.
+    pub baseline_ranking_points: f64,
+}
+
+impl TeamSchedule {
+    /// How many ranking points the schedule is worth compared to a random one. Positive is lucky.
+    pub fn luck(&self) -> f64 {
+        self.expected_ranking_points - self.baseline_ranking_points
+    }
+}
+
+/// Each team's OPR and ranking point bonus contributions, by index.
+struct Strengths {
+    opr: Vec<f64>,
+    bonuses: Vec<Vec<f64>>,
+    margin_sd: f64,
+    /// Ranking points for a win in the season being played.
+    win_ranking_points: f64,
+}
+
+impl Strengths {
+    fn sum(values: &[f64], alliance: &[usize]) -> f64 {
+        alliance.iter().map(|&t| values[t]).sum()
+    }
+
+    /// Ranking points `alliance` is expected to earn against `opponents`.
+    fn expected_ranking_points(&self, alliance: &[usize], opponents: &[usize]) -> f64 {
+        let margin = Strengths::sum(&self.opr, alliance) - Strengths::sum(&self.opr, opponents);
+        let bonuses: f64 = self.bonuses.iter().map(|b| Strengths::sum(b, alliance).clamp(0.0, 1.0)).sum();
+        self.win_ranking_points * predict::margin_win_probability(margin, self.margin_sd) + bonuses
+    }
+}
+
+/// Measures every team's qualification schedule in `schedule`, played or not, by the OPRs in
+/// `oprs` and ranking point bonuses in `bonuses`, with wins worth what they are in `season`.
+/// The random baseline plays each team's matches `runs` times with new partners and opponents;
+/// the same seed always gives the same baseline.
+pub fn analyze(season: &dyn Season, schedule: &[Match], oprs: &ComponentTable, bonuses: Option<&ComponentTable>, participation: Participation, runs: usize, seed: u64) -> Vec<TeamSchedule> {
+    let quals: Vec<&Match> = schedule.iter()
+        .filter(|m| m.comp_level == CompLevel::QualificationMatch && m.alliances.is_some())
+        .collect();
+
+    let mut teams: Vec<TeamKey> = quals.iter()
+        .filter_map(|m| m.team_keys())
+        .flat_map(|t| t.into_iter().cloned())
+        .collect();
+    teams.sort_unstable();
+    teams.dedup();
+    let index: HashMap<&TeamKey, usize> = teams.iter().enumerate().map(|(i, t)| (t, i)).collect();
+
+    let strengths = Strengths {
+        opr: teams.iter().map(|t| oprs.value_or_mean(t, 0).unwrap_or(0.0)).collect(),
+        bonuses: bonuses
+            .map(|b| (0..b.columns.len()).map(|c| teams.iter().map(|t| b.value_or_mean(t, c).unwrap_or(0.0)).collect()).collect())
+            .unwrap_or_default(),
+        margin_sd: oprs.stats[0].map_or(f64::NAN, |s| s.residual_sd) * std::f64::consts::SQRT_2,
+        win_ranking_points: f64::from(season.win_ranking_points()),
+    };
+
+    let mut results: Vec<TeamSchedule> = teams.iter()
+        .map(|t| TeamSchedule {
+            team: t.clone(),
+            matches: 0,
+            partner_strength: 0.0,
+            opponent_strength: 0.0,
+            expected_ranking_points: 0.0,
+            baseline_ranking_points: 0.0,
+        })
+        .collect();
+
+    for m in &quals {
+        let alliances = m.alliances.as_ref().unwrap();
+        for &color in &AllianceColor::BOTH {
+            let alliance = alliances.get(color);
+            let own: Vec<usize> = alliance.team_keys.iter().map(|t| index[t]).collect();
+            let opponents: Vec<usize> = alliances.get(color.opponent()).team_keys.iter().map(|t| index[t]).collect();
+            let expected = strengths.expected_ranking_points(&own, &opponents);
+            let opponent_strength = Strengths::sum(&strengths.opr, &opponents);
+
+            for (t, &i) in alliance.team_keys.iter().zip(own.iter()) {
+                if !participation.is_counted(alliance, t) {
+                    continue;
+                }
+                let result = &mut results[i];
+                result.matches += 1;
+                result.partner_strength += Strengths::sum(&strengths.opr, &own) - strengths.opr[i];
+                result.opponent_strength += opponent_strength;
+                result.expected_ranking_points += expected;
+            }
+        }
+    }
+
+    // Every other team is equally likely to be a partner or an opponent.
+    let mut rng = StdRng::seed_from_u64(seed);
+    if teams.len() >= 6 && runs > 0 {
+        for (i, result) in results.iter_mut().enumerate() {
+            let mut total = 0.0;
+            for _ in 0..runs * result.matches {
+                let others: Vec<usize> = sample(&mut rng, teams.len() - 1, 5).into_iter()
+                    .map(|o| if o >= i { o + 1 } else { o })
+                    .collect();
+                total += strengths.expected_ranking_points(&[i, others[0], others[1]], &others[2..]);
+            }
+            result.baseline_ranking_points = total / runs as f64;
+        }
+    }
+
+    for result in &mut results {
+        if result.matches > 0 {
+            result.partner_strength /= result.matches as f64;
+            result.opponent_strength /= result.matches as f64;
+        }
+    }
+    results.sort_by(|a, b| b.luck().partial_cmp(&a.luck()).unwrap_or(std::cmp::Ordering::Equal));
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use season;
+
+    fn strengths(win_ranking_points: f64) -> Strengths {
+        Strengths {
+            opr: vec![30.0, 20.0, 10.0, 10.0, 10.0, 10.0],
+            bonuses: vec![vec![0.2; 6]],
+            margin_sd: 10.0,
+            win_ranking_points,
+        }
+    }
+
+    #[test]
+    fn wins_are_worth_the_seasons_ranking_points() {
+        let (red, blue) = ([0, 1, 2], [3, 4, 5]);
+        for &season in &[season::for_year(2015), season::for_year(2019)] {
+            let s = strengths(f64::from(season.win_ranking_points()));
+            let total = s.expected_ranking_points(&red, &blue) + s.expected_ranking_points(&blue, &red);
+            // Between them the alliances earn one win's worth plus 0.6 of a bonus each.
+            assert!((total - s.win_ranking_points - 1.2).abs() < 1e-6);
+        }
+        assert_eq!(season::for_year(2015).win_ranking_points(), 0);
+    }
+
+    #[test]
+    fn unknown_spread_splits_the_win() {
+        // Early in an event the OPR fit has no residual degrees of freedom and a NaN spread.
+        let s = Strengths { margin_sd: f64::NAN, ..strengths(2.0) };
+        assert!((s.expected_ranking_points(&[0, 1, 2], &[3, 4, 5]) - 1.6).abs() < 1e-6);
+        assert!((s.expected_ranking_points(&[3, 4, 5], &[0, 1, 2]) - 1.6).abs() < 1e-6);
+    }
+
+    #[test]
+    fn even_alliances_split_the_win() {
+        let s = strengths(2.0);
+        let even = [2, 3, 4];
+        assert!((s.expected_ranking_points(&even, &[3, 4, 5]) - 1.6).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,355 @@
+use the_blue_alliance::matches::{AllianceColor, Match, ScoreBreakdown, ScoreBreakdownAlliance};
+use elo;
+use state::TeamRankingData;
+
+/// What one team earned toward the rankings in one match.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MatchResult {
+    pub ranking_points: u32,
+    /// The alliance's score.
+    pub score: u32,
+    /// Points toward each of the season's tiebreakers, in order.
+    pub tiebreakers: Vec<u32>,
+}
+
+/// How a season's qualification rankings are worked out: what teams are sorted by first, and
+/// which totals break ties after that.
+pub trait Season: Sync {
+    /// The name of the first sort key.
+    fn ranking_score_name(&self) -> &'static str {
+        "RS"
+    }
+
+    /// The names of the tiebreakers, in the order they are applied.
+    fn tiebreakers(&self) -> &'static [&'static str];
+
+    /// What a team on `color` earned toward the rankings in `m`, or `None` if `m` has not been
+    /// played or is missing data the season needs.
+    fn result(&self, m: &Match, color: AllianceColor, disqualified: bool) -> Option<MatchResult>;
+
+    /// The first sort key. Most seasons use the average ranking points per match.
+    fn ranking_score(&self, data: &TeamRankingData) -> f64 {
+        f64::from(data.ranking_points) / f64::from(data.played)
+    }
+
+    /// Ranking points for winning a match. A tie earns half as many.
+    fn win_ranking_points(&self) -> u32 {
+        2
+    }
+
+    /// How many sort orders, the ranking score and then each tiebreaker, follow the game manual
+    /// and so can be checked against the official ones.
+    fn checked_sort_orders(&self) -> usize {
+        1 + self.tiebreakers().len()
+    }
+}
+
+/// The ranking rules for `year`. Seasons since 2020 rank by the ranking points in their score
+/// breakdowns; earlier seasons without their own rules rank by wins and ties, then match points.
+pub fn for_year(year: i32) -> &'static dyn Season {
+    match year {
+        2015 => &Season2015,
+        2016 => &Season2016,
+        2017 => &Season2017,
+        2018 => &Season2018,
+        2019 => &Season2019,
+        2020..=2024 => &Modern { win_ranking_points: 2 },
+        _ if year >= 2025 => &Modern { win_ranking_points: 3 },
+        _ => &Generic,
+    }
+}
+
+/// `win` ranking points for a win and half as many for a tie.
+pub fn outcome_ranking_points(m: &Match, color: AllianceColor, win: u32) -> Option<u32> {
+    let red = elo::red_outcome(m)?;
+    let outcome = if color == AllianceColor::Red { red } else { 1.0 - red };
+    Some((outcome * f64::from(win)).round() as u32)
+}
+
+fn score(m: &Match, color: AllianceColor) -> Option<u32> {
+    m.score_for(color).map(|s| s as u32)
+}
+
+fn bonus(earned: bool) -> u32 {
+    if earned { 1 } else { 0 }
+}
+
+/// Recycle Rush ranks by qualification average, the mean alliance score. A disqualified team
+/// scores nothing for the match.
+pub struct Season2015;
+
+impl Season for Season2015 {
+    fn ranking_score_name(&self) -> &'static str {
+        "QA"
+    }
+
+    fn tiebreakers(&self) -> &'static [&'static str] {
+        &["Coopertition", "Auto", "Container", "Tote", "Litter"]
+    }
+
+    fn win_ranking_points(&self) -> u32 {
+        0
+    }
+
+    fn result(&self, m: &Match, color: AllianceColor, disqualified: bool) -> Option<MatchResult> {
+        let coopertition = match m.score_breakdown {
+            Some(ScoreBreakdown::Year2015(ref d)) => d.coopertition_points as u32,
+            _ => return None,
+        };
+        let d = match m.breakdown_for(color)? {
+            ScoreBreakdownAlliance::Year2015(d) => d,
+            _ => return None,
+        };
+        Some(MatchResult {
+            ranking_points: 0,
+            score: if disqualified { 0 } else { score(m, color)? },
+            tiebreakers: vec![coopertition, d.auto_points as u32, d.container_points as u32, d.tote_points as u32, d.litter_points as u32],
+        })
+    }
+
+    fn ranking_score(&self, data: &TeamRankingData) -> f64 {
+        f64::from(data.score) / f64::from(data.played)
+    }
+}
+
+/// Stronghold ranks by total ranking points, with bonuses for breaching and capturing.
+pub struct Season2016;
+
+impl Season for Season2016 {
+    fn tiebreakers(&self) -> &'static [&'static str] {
+        &["Auto", "Scale/Challenge", "Goals", "Defense"]
+    }
+
+    fn result(&self, m: &Match, color: AllianceColor, disqualified: bool) -> Option<MatchResult> {
+        let d = match m.breakdown_for(color)? {
+            ScoreBreakdownAlliance::Year2016(d) => d,
+            _ => return None,
+        };
+        let ranking_points = outcome_ranking_points(m, color, self.win_ranking_points())? + bonus(d.teleop_defenses_breached > 0) + bonus(d.teleop_tower_captured > 0);
+        Some(MatchResult {
+            ranking_points: if disqualified { 0 } else { ranking_points },
+            score: score(m, color)?,
+            tiebreakers: vec![
+                d.auto_points as u32,
+                (d.teleop_challenge_points + d.teleop_scale_points) as u32,
+                (d.auto_boulder_points + d.teleop_boulder_points) as u32,
+                (d.auto_crossing_points + d.teleop_crossing_points) as u32,
+            ],
+        })
+    }
+
+    fn ranking_score(&self, data: &TeamRankingData) -> f64 {
+        f64::from(data.ranking_points)
+    }
+}
+
+/// Steamworks ranks by average ranking points, with bonuses for pressure and rotors.
+pub struct Season2017;
+
+impl Season for Season2017 {
+    fn tiebreakers(&self) -> &'static [&'static str] {
+        &["Match Points", "Auto", "Rotor", "Touchpad", "Pressure"]
+    }
+
+    fn result(&self, m: &Match, color: AllianceColor, disqualified: bool) -> Option<MatchResult> {
+        let d = match m.breakdown_for(color)? {
+            ScoreBreakdownAlliance::Year2017(d) => d,
+            _ => return None,
+        };
+        let ranking_points = outcome_ranking_points(m, color, self.win_ranking_points())? + bonus(d.kpa_ranking_point_achieved) + bonus(d.rotor_ranking_point_achieved);
+        let score = score(m, color)?;
+        Some(MatchResult {
+            ranking_points: if disqualified { 0 } else { ranking_points },
+            score,
+            tiebreakers: vec![
+                score,
+                d.auto_points as u32,
+                (d.auto_rotor_points + d.teleop_rotor_points) as u32,
+                d.teleop_takeoff_points as u32,
+                (d.auto_fuel_points + d.teleop_fuel_points) as u32,
+            ],
+        })
+    }
+}
+
+/// Power Up ranks by average ranking points, with bonuses for the auto quest and facing the boss.
+pub struct Season2018;
+
+impl Season for Season2018 {
+    fn tiebreakers(&self) -> &'static [&'static str] {
+        &["Park/Climb", "Auto", "Ownership", "Vault"]
+    }
+
+    fn result(&self, m: &Match, color: AllianceColor, disqualified: bool) -> Option<MatchResult> {
+        let d = match m.breakdown_for(color)? {
+            ScoreBreakdownAlliance::Year2018(d) => d,
+            _ => return None,
+        };
+        Some(MatchResult {
+            ranking_points: if disqualified { 0 } else { d.rp as u32 },
+            score: score(m, color)?,
+            tiebreakers: vec![
+                d.endgame_points as u32,
+                d.auto_points as u32,
+                (d.auto_ownership_points + d.teleop_ownership_points) as u32,
+                d.vault_points as u32,
+            ],
+        })
+    }
+}
+
+/// Destination: Deep Space ranks by average ranking points, with bonuses for completing a rocket
+/// and docking on the HAB.
+pub struct Season2019;
+
+impl Season for Season2019 {
+    fn tiebreakers(&self) -> &'static [&'static str] {
+        &["Cargo", "Panel", "Climb", "Sandstorm"]
+    }
+
+    fn result(&self, m: &Match, color: AllianceColor, disqualified: bool) -> Option<MatchResult> {
+        let d = match m.breakdown_for(color)? {
+            ScoreBreakdownAlliance::Year2019(d) => d,
+            _ => return None,
+        };
+        Some(MatchResult {
+            ranking_points: if disqualified { 0 } else { d.rp as u32 },
+            score: score(m, color)?,
+            tiebreakers: vec![d.cargo_points as u32, d.hatch_panel_points as u32, d.hab_climb_points as u32, d.sand_storm_bonus_points as u32],
+        })
+    }
+}
+
+/// Seasons since 2020, ranked by average ranking points as the score breakdown reports them,
+/// which include the bonuses and the season's points for a win. Each season has its own
+/// tiebreakers, which are approximated by match points, so only the ranking score follows the
+/// manual.
+pub struct Modern {
+    win_ranking_points: u32,
+}
+
+impl Season for Modern {
+    fn tiebreakers(&self) -> &'static [&'static str] {
+        &["Match Points"]
+    }
+
+    fn win_ranking_points(&self) -> u32 {
+        self.win_ranking_points
+    }
+
+    fn checked_sort_orders(&self) -> usize {
+        1
+    }
+
+    fn result(&self, m: &Match, color: AllianceColor, disqualified: bool) -> Option<MatchResult> {
+        let ranking_points = match m.breakdown_for(color)? {
+            ScoreBreakdownAlliance::Other(d) => d.rp? as u32,
+            _ => return None,
+        };
+        let score = score(m, color)?;
+        Some(MatchResult {
+            ranking_points: if disqualified { 0 } else { ranking_points },
+            score,
+            tiebreakers: vec![score],
+        })
+    }
+}
+
+/// Any season before 2015, ranked by average ranking points from wins and ties, then match
+/// points. This needs no score breakdown, but is not the real ranking for any of them.
+pub struct Generic;
+
+impl Season for Generic {
+    fn tiebreakers(&self) -> &'static [&'static str] {
+        &["Match Points"]
+    }
+
+    fn checked_sort_orders(&self) -> usize {
+        0
+    }
+
+    fn result(&self, m: &Match, color: AllianceColor, disqualified: bool) -> Option<MatchResult> {
+        let score = score(m, color)?;
+        Some(MatchResult {
+            ranking_points: if disqualified { 0 } else { outcome_ranking_points(m, color, self.win_ranking_points())? },
+            score,
+            tiebreakers: vec![score],
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use testing::{qual_2019, qual_at};
+
+    fn ranking(ranking_score: f64, tiebreakers: Vec<u32>) -> TeamRankingData {
+        TeamRankingData { ranking_score, tiebreakers, ..TeamRankingData::default() }
+    }
+
+    #[test]
+    fn close_ranking_scores_are_not_tied() {
+        let mut teams = [
+            ranking(2.0012, vec![0]),
+            ranking(2.0, vec![20]),
+            ranking(2.0006, vec![10]),
+            ranking(2.0, vec![30]),
+        ];
+        teams.sort();
+        let order: Vec<f64> = teams.iter().map(|t| t.ranking_score).collect();
+        assert_eq!(order, vec![2.0, 2.0, 2.0006, 2.0012]);
+        assert_eq!(teams[0].tiebreakers, vec![20]);
+    }
+
+    #[test]
+    fn season_2019_counts_reported_ranking_points() {
+        let m = qual_2019(1, [1, 2, 3], [4, 5, 6], [24, 12, 15, 6, 3], [15, 10, 9, 6, 0]);
+        let red = Season2019.result(&m, AllianceColor::Red, false).unwrap();
+        assert_eq!(red.ranking_points, 2);
+        assert_eq!(red.score, 60);
+        assert_eq!(red.tiebreakers, vec![24, 12, 15, 6]);
+        assert_eq!(Season2019.result(&m, AllianceColor::Red, true).unwrap().ranking_points, 0);
+        assert_eq!(Season2019.result(&m, AllianceColor::Blue, false).unwrap().ranking_points, 0);
+    }
+
+    /// A 2020 or later qualification match whose breakdown reports `rp` for each alliance.
+    fn modern(year: i32, rp: (Option<i32>, Option<i32>)) -> Match {
+        let mut value = serde_json::to_value(qual_at(&format!("{}test", year), 1, [1, 2, 3], [4, 5, 6], (80, 70), 1_700_000_000)).unwrap();
+        value["score_breakdown"] = serde_json::json!({
+            "red": { "autoPoints": 20, "teleopPoints": 55, "foulPoints": 5, "totalPoints": 80, "rp": rp.0 },
+            "blue": { "autoPoints": 15, "teleopPoints": 55, "foulPoints": 0, "totalPoints": 70, "rp": rp.1 },
+        });
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn later_seasons_rank_by_reported_ranking_points() {
+        let m = modern(2025, (Some(5), Some(1)));
+        let season = for_year(2025);
+        assert_eq!(season.win_ranking_points(), 3);
+        assert_eq!(season.result(&m, AllianceColor::Red, false).unwrap().ranking_points, 5);
+        assert_eq!(season.result(&m, AllianceColor::Blue, false).unwrap().ranking_points, 1);
+        assert_eq!(season.result(&m, AllianceColor::Red, true).unwrap().ranking_points, 0);
+
+        assert_eq!(for_year(2023).win_ranking_points(), 2);
+        assert!(season.result(&modern(2025, (None, None)), AllianceColor::Red, false).is_none());
+    }
+
+    #[test]
+    fn only_modeled_sort_orders_are_checked() {
+        assert_eq!(for_year(2019).checked_sort_orders(), 5);
+        assert_eq!(for_year(2015).checked_sort_orders(), 6);
+        assert_eq!(for_year(2023).checked_sort_orders(), 1);
+        assert_eq!(for_year(2010).checked_sort_orders(), 0);
+    }
+
+    #[test]
+    fn ranking_score_averages_ranking_points() {
+        let m = qual_2019(1, [1, 2, 3], [4, 5, 6], [24, 12, 15, 6, 3], [15, 10, 9, 6, 0]);
+        let mut data = TeamRankingData::default();
+        data.add(&Season2019, &Season2019.result(&m, AllianceColor::Red, false).unwrap());
+        data.add(&Season2019, &Season2019.result(&m, AllianceColor::Blue, false).unwrap());
+        assert_eq!((data.played, data.ranking_points, data.ranking_score), (2, 2, 1.0));
+        assert_eq!(data.tiebreakers, vec![39, 22, 24, 12]);
+    }
+}
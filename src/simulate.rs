@@ -0,0 +1,351 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+use futures::Future;
+use futures_cpupool::CpuPool;
+use rand::SeedableRng;
+use rand::distributions::StandardNormal;
+use rand::rngs::StdRng;
+use rand::Rng;
+use the_blue_alliance::keys::TeamKey;
+use the_blue_alliance::matches::{AllianceColor, Match};
+use opr::{ComponentTable, Design, OprError};
+use season::{self, MatchResult, Season};
+use state::{EventState, Participation, TeamRankingData};
+
+/// Runs are played in chunks of this many, each with its own seed, so that the results do not
+/// depend on how the pool schedules them.
+const CHUNK: usize = 250;
+
+/// Columns of the table `ranking_table` solves: the score, then bonus ranking points, then the
+/// season's tiebreakers in order.
+const SCORE: usize = 0;
+const BONUS: usize = 1;
+const TIEBREAKERS: usize = 2;
+
+/// Why rankings could not be simulated.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SimulateError {
+    /// What alliances earn toward the rankings could not be solved from the played matches.
+    Opr(OprError),
+    /// Zero runs were asked for, which gives no distribution.
+    NoRuns,
+}
+
+impl fmt::Display for SimulateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SimulateError::Opr(e) => write!(f, "Cannot compute OPRs: {}", e),
+            SimulateError::NoRuns => write!(f, "At least one run is needed"),
+        }
+    }
+}
+
+/// Solves what each team adds to its alliance's score, bonus ranking points and each of the
+/// season's tiebreakers over the played matches in `design`, by the season's own results.
+/// Bonus ranking points are whatever an alliance earned beyond its win or tie.
+fn ranking_table(season: &'static dyn Season, design: &Design) -> Result<ComponentTable, OprError> {
+    let result = move |m: &Match, color| season.result(m, color, false);
+    let mut columns = vec![
+        ("Score", design.fit(|m, c| Some(f64::from(result(m, c)?.score)))?),
+        ("Bonus RP", design.fit(|m, c| {
+            let outcome = season::outcome_ranking_points(m, c, season.win_ranking_points())?;
+            Some(f64::from(result(m, c)?.ranking_points) - f64::from(outcome))
+        })?),
+    ];
+    for (i, &name) in season.tiebreakers().iter().enumerate() {
+        columns.push((name, design.fit(|m, c| Some(f64::from(*result(m, c)?.tiebreakers.get(i)?)))?));
+    }
+    Ok(ComponentTable::from_columns(columns, false))
+}
+
+/// The distribution of one alliance's result in one scheduled match.
+struct AllianceForecast {
+    /// Indices of the teams whose rankings the match counts toward.
+    teams: Vec<usize>,
+    /// Mean and standard deviation of each column of the `ranking_table`.
+    columns: Vec<(f64, f64)>,
+}
+
+impl AllianceForecast {
+    fn new(m: &Match, color: AllianceColor, index: &HashMap<TeamKey, usize>, oprs: &ComponentTable, participation: Participation) -> AllianceForecast {
+        let alliance = m.alliances.as_ref().unwrap().get(color);
+        AllianceForecast {
+            teams: alliance.team_keys.iter()
+                .filter(|t| participation.is_counted(alliance, t))
+                .map(|t| index[t])
+                .collect(),
+            columns: (0..oprs.columns.len())
+                .map(|column| {
+                    let mean = alliance.team_keys.iter().filter_map(|t| oprs.value_or_mean(t, column)).sum();
+                    let sd = oprs.stats[column].map(|s| s.residual_sd).filter(|sd| sd.is_finite() && *sd > 0.0).unwrap_or(0.0);
+                    (mean, sd)
+                })
+                .collect(),
+        }
+    }
+
+    /// Draws the alliance's score and what it earns toward the rankings, before win or tie
+    /// ranking points. An alliance that plays well scores well in every part of the game, so
+    /// the score and tiebreakers share one draw. Bonus ranking points are the whole part of the
+    /// expected number, and one more with the chance of the fraction left.
+    fn sample<R: Rng>(&self, rng: &mut R) -> MatchResult {
+        let z: f64 = rng.sample(StandardNormal);
+        let draw = |&(mean, sd): &(f64, f64)| (mean + sd * z).max(0.0).round() as u32;
+        let bonus = self.columns[BONUS].0.max(0.0);
+        let extra = if rng.gen::<f64>() < bonus.fract() { 1 } else { 0 };
+
+        MatchResult {
+            ranking_points: bonus.floor() as u32 + extra,
+            score: draw(&self.columns[SCORE]),
+            tiebreakers: self.columns[TIEBREAKERS..].iter().map(draw).collect(),
+        }
+    }
+}
+
+struct Simulation {
+    season: &'static dyn Season,
+    current: Vec<TeamRankingData>,
+    schedule: Vec<(AllianceForecast, AllianceForecast)>,
+}
+
+impl Simulation {
+    /// Plays out the schedule once and returns every team's final rank, starting at 0.
+    fn play<R: Rng>(&self, rng: &mut R, final_data: &mut Vec<TeamRankingData>) -> Vec<usize> {
+        final_data.clone_from(&self.current);
+        for (red, blue) in &self.schedule {
+            let mut red_result = red.sample(rng);
+            let mut blue_result = blue.sample(rng);
+            let win = self.season.win_ranking_points();
+            let (red_rp, blue_rp) = if red_result.score > blue_result.score {
+                (win, 0)
+            } else if red_result.score < blue_result.score {
+                (0, win)
+            } else {
+                (win / 2, win / 2)
+            };
+            red_result.ranking_points += red_rp;
+            blue_result.ranking_points += blue_rp;
+
+            for &t in &red.teams {
+                final_data[t].add(self.season, &red_result);
+            }
+            for &t in &blue.teams {
+                final_data[t].add(self.season, &blue_result);
+            }
+        }
+
+        let mut order: Vec<usize> = (0..final_data.len()).collect();
+        order.sort_by(|&a, &b| final_data[b].cmp(&final_data[a]));
+        let mut ranks = vec![0; order.len()];
+        for (rank, &t) in order.iter().enumerate() {
+            ranks[t] = rank;
+        }
+        ranks
+    }
+
+    fn tally(&self, runs: usize, seed: u64) -> Tally {
+        let teams = self.current.len();
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut tally = Tally {
+            rank_counts: vec![vec![0; teams]; teams],
+            ranking_points: vec![0; teams],
+        };
+        let mut final_data = Vec::with_capacity(teams);
+        for _ in 0..runs {
+            let ranks = self.play(&mut rng, &mut final_data);
+            for (t, &rank) in ranks.iter().enumerate() {
+                tally.rank_counts[t][rank] += 1;
+                tally.ranking_points[t] += u64::from(final_data[t].ranking_points);
+            }
+        }
+        tally
+    }
+}
+
+struct Tally {
+    rank_counts: Vec<Vec<usize>>,
+    ranking_points: Vec<u64>,
+}
+
+/// How a team is likely to finish qualifications.
+pub struct TeamOutlook {
+    pub team: TeamKey,
+    /// How many runs the team finished at each rank, starting with first.
+    pub rank_counts: Vec<usize>,
+    pub expected_ranking_points: f64,
+    runs: usize,
+}
+
+impl TeamOutlook {
+    pub fn mean_rank(&self) -> f64 {
+        self.rank_counts.iter().enumerate().map(|(r, &c)| (r + 1) as f64 * c as f64).sum::<f64>() / self.runs as f64
+    }
+
+    /// The probability of finishing at `rank` or better.
+    pub fn probability_of_top(&self, rank: usize) -> f64 {
+        self.rank_counts.iter().take(rank).sum::<usize>() as f64 / self.runs as f64
+    }
+
+    /// The best rank the team finishes at or below in at least `p` of runs.
+    pub fn percentile(&self, p: f64) -> usize {
+        let mut seen = 0;
+        for (r, &c) in self.rank_counts.iter().enumerate() {
+            seen += c;
+            if seen as f64 >= p * self.runs as f64 {
+                return r + 1;
+            }
+        }
+        self.rank_counts.len()
+    }
+}
+
+/// Plays out the rest of qualifications `runs` times on `pool`, starting from `state` and
+/// drawing what each alliance earns toward the rankings from OPRs solved over the played
+/// qualification matches in `design`, by the rules of the event's season. The same seed always
+/// gives the same results.
+pub fn simulate(pool: &CpuPool, state: &EventState, schedule: &[Match], design: &Design, participation: Participation, runs: usize, seed: u64) -> Result<Vec<TeamOutlook>, SimulateError> {
+    if runs == 0 {
+        return Err(SimulateError::NoRuns);
+    }
+    let oprs = ranking_table(state.season(), design).map_err(SimulateError::Opr)?;
+
+    let mut teams: Vec<TeamKey> = state.ranked().map(|t| t.team.clone())
+        .chain(schedule.iter().filter_map(|m| m.team_keys()).flat_map(|t| t.into_iter().cloned()))
+        .collect();
+    teams.sort_unstable();
+    teams.dedup();
+    let index: HashMap<TeamKey, usize> = teams.iter().enumerate().map(|(i, t)| (t.clone(), i)).collect();
+
+    let mut current = vec![TeamRankingData::default(); teams.len()];
+    for t in state.ranked() {
+        current[index[&t.team]] = t.ranking.clone();
+    }
+
+    let simulation = Arc::new(Simulation {
+        season: state.season(),
+        current,
+        schedule: schedule.iter()
+            .filter(|m| m.alliances.is_some())
+            .map(|m| (
+                AllianceForecast::new(m, AllianceColor::Red, &index, &oprs, participation),
+                AllianceForecast::new(m, AllianceColor::Blue, &index, &oprs, participation),
+            ))
+            .collect(),
+    });
+
+    let chunks = (0..runs.div_ceil(CHUNK)).map(|chunk| {
+        let simulation = simulation.clone();
+        let chunk_runs = std::cmp::min(CHUNK, runs - chunk * CHUNK);
+        pool.spawn_fn(move || Ok::<_, ()>(simulation.tally(chunk_runs, seed.wrapping_add(chunk as u64))))
+    });
+    let tallies = futures::future::join_all(chunks).wait().unwrap();
+
+    let mut outlooks: Vec<TeamOutlook> = teams.into_iter().enumerate()
+        .map(|(t, team)| TeamOutlook {
+            team,
+            rank_counts: (0..index.len()).map(|r| tallies.iter().map(|tally| tally.rank_counts[t][r]).sum()).collect(),
+            expected_ranking_points: tallies.iter().map(|tally| tally.ranking_points[t]).sum::<u64>() as f64 / runs as f64,
+            runs,
+        })
+        .collect();
+    outlooks.sort_by(|a, b| a.mean_rank().partial_cmp(&b.mean_rank()).unwrap_or(std::cmp::Ordering::Equal));
+    Ok(outlooks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use testing::{qual, qual_2019, qual_with_rp};
+
+    fn played() -> Vec<Match> {
+        vec![
+            qual_2019(1, [1, 2, 3], [4, 5, 6], [24, 12, 15, 6, 3], [15, 10, 9, 6, 0]),
+            qual_2019(2, [1, 4, 5], [2, 3, 6], [18, 14, 12, 6, 0], [21, 8, 6, 3, 5]),
+            qual_2019(3, [1, 2, 6], [3, 4, 5], [21, 10, 12, 9, 3], [18, 12, 15, 3, 0]),
+            qual_2019(4, [2, 4, 6], [1, 3, 5], [9, 8, 6, 3, 0], [30, 16, 15, 9, 0]),
+            qual_2019(5, [1, 3, 4], [2, 5, 6], [27, 14, 15, 6, 3], [12, 10, 6, 6, 0]),
+            qual_2019(6, [3, 5, 6], [1, 2, 4], [15, 10, 9, 6, 0], [24, 12, 15, 9, 0]),
+            qual_2019(7, [1, 5, 6], [2, 3, 4], [18, 12, 9, 6, 0], [21, 14, 12, 6, 3]),
+        ]
+    }
+
+    fn run_on(pool: &CpuPool, year: i32, played: &[Match], runs: usize, seed: u64) -> Result<Vec<TeamOutlook>, SimulateError> {
+        let schedule = vec![qual(8, [1, 2, 5], [3, 4, 6], (-1, -1)), qual(9, [2, 3, 5], [1, 4, 6], (-1, -1))];
+        let state = EventState::empty(year, Participation::default());
+        let design = Design::for_matches(played, Participation::default(), None).unwrap();
+        simulate(pool, &state, &schedule, &design, Participation::default(), runs, seed)
+    }
+
+    fn run(year: i32, played: &[Match], runs: usize) -> Result<Vec<TeamOutlook>, SimulateError> {
+        run_on(&CpuPool::new(2), year, played, runs, 0)
+    }
+
+    #[test]
+    fn counts_every_run_once() {
+        let runs = CHUNK + 10;
+        let outlooks = run(2019, &played(), runs).unwrap();
+        assert_eq!(outlooks.len(), 6);
+        for o in &outlooks {
+            assert_eq!(o.rank_counts.iter().sum::<usize>(), runs);
+            assert!(o.mean_rank() >= 1.0 && o.mean_rank() <= 6.0);
+        }
+    }
+
+    #[test]
+    fn same_seed_gives_same_results() {
+        let pool = CpuPool::new(4);
+        let runs = 3 * CHUNK + 7;
+        let counts = |outlooks: Vec<TeamOutlook>| {
+            let mut counts: Vec<(TeamKey, Vec<usize>)> = outlooks.into_iter().map(|o| (o.team, o.rank_counts)).collect();
+            counts.sort();
+            counts
+        };
+        let first = counts(run_on(&pool, 2019, &played(), runs, 7).unwrap());
+        let second = counts(run_on(&pool, 2019, &played(), runs, 7).unwrap());
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn simulates_later_seasons() {
+        // Two ranking points for a win, and the winners of the first two matches earned a bonus.
+        let played = vec![
+            qual_with_rp(2023, 1, [1, 2, 3], [4, 5, 6], (80, 60), (3, 0)),
+            qual_with_rp(2023, 2, [1, 4, 5], [2, 3, 6], (70, 65), (3, 0)),
+            qual_with_rp(2023, 3, [1, 2, 6], [3, 4, 5], (75, 50), (2, 0)),
+            qual_with_rp(2023, 4, [2, 4, 6], [1, 3, 5], (55, 90), (0, 2)),
+            qual_with_rp(2023, 5, [1, 3, 4], [2, 5, 6], (85, 40), (2, 0)),
+        ];
+        let outlooks = run(2023, &played, 100).unwrap();
+        assert_eq!(outlooks.len(), 6);
+        // Team 1 won every match it played and is expected to keep winning.
+        let first = outlooks.iter().find(|o| o.team == TeamKey::from_number(1)).unwrap();
+        assert!(first.expected_ranking_points > 0.0);
+        assert!(first.mean_rank() < 3.0);
+    }
+
+    #[test]
+    fn draws_bonus_ranking_points_around_their_expected_number() {
+        let forecast = AllianceForecast {
+            teams: vec![0],
+            columns: vec![(50.0, 10.0), (1.25, 0.0)],
+        };
+        let mut rng = StdRng::seed_from_u64(3);
+        let draws: Vec<u32> = (0..4000).map(|_| forecast.sample(&mut rng).ranking_points).collect();
+        assert!(draws.iter().all(|&rp| rp == 1 || rp == 2));
+        let mean = f64::from(draws.iter().sum::<u32>()) / draws.len() as f64;
+        assert!((mean - 1.25).abs() < 0.05);
+    }
+
+    #[test]
+    fn needs_season_results() {
+        // 2019 rankings need score breakdowns.
+        let played: Vec<Match> = played().into_iter().map(|mut m| { m.score_breakdown = None; m }).collect();
+        assert_eq!(run(2019, &played, 10).err(), Some(SimulateError::Opr(OprError::NoMatches)));
+    }
+
+    #[test]
+    fn rejects_zero_runs() {
+        assert_eq!(run(2019, &played(), 0).err(), Some(SimulateError::NoRuns));
+    }
+}
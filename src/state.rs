@@ -1,166 +1,494 @@
 
 use std::cmp::Ordering;
 use std::collections::HashMap;
-use futures::{Future, Stream};
-use chrono::TimeZone;
+use std::fs::File;
+use std::path::Path;
+use futures::Future;
+use the_blue_alliance::keys::{MatchKey, TeamKey};
+use the_blue_alliance::team::MatchAlliance;
+use the_blue_alliance::matches::Match;
+use season::{self, MatchResult, Season};
+
+/// Which match appearances count toward a team's record.
+///
+/// The default follows the game manual: surrogate appearances do not count at all, and
+/// disqualified teams play the match but earn no ranking points. Those rules are about rankings,
+/// so by default OPRs count every robot that was on the field.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Participation {
+    /// Count matches played as a surrogate like any other match.
+    pub count_surrogates: bool,
+    /// Treat disqualified teams as if they had not been disqualified.
+    pub ignore_disqualifications: bool,
+    /// Leave uncounted appearances and disqualified teams out of OPRs as well.
+    #[serde(default)]
+    pub exclude_from_oprs: bool,
+}
+
+impl Participation {
+    /// Whether `team`'s appearance on `alliance` counts toward its record.
+    pub fn is_counted(&self, alliance: &MatchAlliance, team: &TeamKey) -> bool {
+        self.count_surrogates || !alliance.is_surrogate(team)
+    }
+
+    /// Whether `team` should be treated as disqualified from the match.
+    pub fn is_disqualified(&self, alliance: &MatchAlliance, team: &TeamKey) -> bool {
+        !self.ignore_disqualifications && alliance.is_disqualified(team)
+    }
+
+    /// Whether `team` is one of the robots its alliance's score is shared between in OPRs.
+    pub fn is_in_oprs(&self, alliance: &MatchAlliance, team: &TeamKey) -> bool {
+        !self.exclude_from_oprs || (self.is_counted(alliance, team) && !self.is_disqualified(alliance, team))
+    }
+}
+
+/// Whether a match had been played by `time`. A match without any time can't be placed before
+/// the cutoff, so it is left out rather than leaking results from after it.
+pub fn played_before(m: &Match, time: chrono::DateTime<chrono::Utc>) -> bool {
+    match m.played_at(&chrono::Utc).or_else(|| m.scheduled_at(&chrono::Utc)) {
+        Some(t) => t < time,
+        None => false,
+    }
+}
+
+/// The qualification rankings at an event, built up one match at a time.
+#[derive(Clone, Serialize, Deserialize)]
 pub struct EventState {
+    pub year: i32,
+    /// Which match appearances were counted. A saved state is only valid for the same rules.
+    pub participation: Participation,
+    /// Every team with a counted match, first to last, followed by any team whose matches were
+    /// all taken back, which keeps its `ranks`.
     pub ranking: Vec<TeamState>,
+    /// What each match counted in `ranking` added, so that it can be taken back if TBA corrects
+    /// the match.
+    applied: HashMap<MatchKey, Vec<TeamResult>>,
 }
 
+/// What one counted team earned in one match, and whether it was disqualified.
+type TeamResult = (TeamKey, MatchResult, bool);
+
 impl EventState {
-    pub fn new<T: chrono::TimeZone>(event: &the_blue_alliance::event::Event, tba: &the_blue_alliance::TBA, time: chrono::DateTime<T>) -> EventState {
-        let mut teams: HashMap<String, TeamState> = HashMap::new();
-        
-        event.matches(tba).into_stream()
-            .map(|ms| futures::stream::iter_ok::<_, the_blue_alliance::Error>(ms.into_iter()))
-            .flatten()
-            .filter(|m| chrono::Utc.timestamp(m.actual_time.or(m.time).unwrap() as i64, 0) < time.with_timezone(&chrono::Utc))
-            .filter(|m| m.score_breakdown.is_some())
-            .filter(|m| m.comp_level == the_blue_alliance::matches::CompLevel::QualificationMatch)
-            .for_each(|m| {
-                for t in m.team_keys().unwrap() {
-                    teams.entry(t.clone()).or_insert_with(|| TeamState::new(&t)).add_match(m.clone());
+    /// Rankings for an event in `year` before any matches have been played.
+    pub fn empty(year: i32, participation: Participation) -> EventState {
+        EventState {
+            year,
+            participation,
+            ranking: Vec::new(),
+            applied: HashMap::new(),
+        }
+    }
+
+    pub fn new<T: chrono::TimeZone>(event: &the_blue_alliance::event::Event, tba: &the_blue_alliance::TBA, time: chrono::DateTime<T>, participation: Participation) -> EventState {
+        let matches = event.matches(tba).wait().unwrap();
+        let mut state = EventState::empty(event.year, participation);
+        state.update(&matches, time.with_timezone(&chrono::Utc));
+        state
+    }
+
+    /// The ranking rules for the event's season.
+    pub fn season(&self) -> &'static dyn Season {
+        season::for_year(self.year)
+    }
+
+    /// Counts every qualification match in `matches` that was played before `time` and has not
+    /// been counted yet, then moves only the teams that played in them. A match already counted
+    /// whose results have changed since, from a score correction, a replay or a new DQ, is taken
+    /// back and counted again. Matches are counted in the order they were played, and each team's
+    /// rank after a newly counted match is added to its `ranks`. Returns whether anything
+    /// changed.
+    pub fn update(&mut self, matches: &[Match], time: chrono::DateTime<chrono::Utc>) -> bool {
+        let season = self.season();
+        let mut quals: Vec<&Match> = matches.iter()
+            .filter(|m| m.comp_level == the_blue_alliance::matches::CompLevel::QualificationMatch && played_before(m, time))
+            .collect();
+        quals.sort_unstable_by(|a, b| a.cmp_by_time(b));
+
+        let mut any = false;
+        for m in quals {
+            let results = team_results(season, m, self.participation);
+            let previous = match self.applied.get(&m.key) {
+                Some(previous) if *previous == results => continue,
+                None if results.is_empty() => continue,
+                _ => self.applied.remove(&m.key).unwrap_or_default(),
+            };
+            let first = previous.is_empty();
+
+            let mut changed: HashMap<TeamKey, TeamState> = HashMap::new();
+            for (t, result, disqualified) in &previous {
+                let ranking = &mut take(&mut self.ranking, &mut changed, t).ranking;
+                ranking.remove(season, result);
+                if *disqualified {
+                    ranking.disqualified -= 1;
+                }
+            }
+            for (t, result, disqualified) in &results {
+                let ranking = &mut take(&mut self.ranking, &mut changed, t).ranking;
+                ranking.add(season, result);
+                if *disqualified {
+                    ranking.disqualified += 1;
+                }
+            }
+
+            for (_, t) in changed {
+                let i = if t.ranking.played > 0 {
+                    self.ranking.iter().position(|other| other.ranking.played == 0 || other.ranking < t.ranking).unwrap_or(self.ranking.len())
+                } else {
+                    self.ranking.len()
+                };
+                self.ranking.insert(i, t);
+            }
+            if first {
+                for (t, _, _) in &results {
+                    if let Some(i) = self.ranking.iter().position(|s| &s.team == t) {
+                        self.ranking[i].ranks.push(i + 1);
+                    }
                 }
-                futures::future::ok(())
-            }).wait().unwrap();
+            }
+            if !results.is_empty() {
+                self.applied.insert(m.key.clone(), results);
+            }
+            any = true;
+        }
+        any
+    }
 
-        let mut teams_vec: Vec<_> = teams.into_iter().map(|(_, v)| v).collect();
+    /// Loads a state saved by `save`, or `None` if there is none or it cannot be read.
+    pub fn load<P: AsRef<Path>>(path: P) -> Option<EventState> {
+        match File::open(path) {
+            Err(e) => {
+                warn!("Cannot load event state: {}", e);
+                None
+            },
+            Ok(file) => match serde_cbor::from_reader(file) {
+                Ok(state) => Some(state),
+                Err(e) => {
+                    warn!("Cannot deserialize event state: {}", e);
+                    None
+                }
+            },
+        }
+    }
 
-        teams_vec.sort_by_key(|t| t.ranking.clone());
-        teams_vec.reverse();
-        EventState{
-            ranking: teams_vec,
+    pub fn save<P: AsRef<Path>>(&self, path: P) {
+        match File::create(path) {
+            Err(e) => warn!("Cannot save event state: {}", e),
+            Ok(mut file) => if let Err(e) = serde_cbor::to_writer(&mut file, self) {
+                warn!("Cannot serialize event state: {}", e);
+            },
         }
     }
 
-    pub fn rank_of_team(&self, team: &str) -> Option<usize> {
-        self.ranking.iter().enumerate().find(|(_, t)| t.team == team).map(|(i, _)| i+1)
+    /// The teams with a counted match, first to last.
+    pub fn ranked(&self) -> impl Iterator<Item = &TeamState> + '_ {
+        self.ranking.iter().take_while(|t| t.ranking.played > 0)
+    }
+
+    pub fn rank_of_team(&self, team: &TeamKey) -> Option<usize> {
+        self.ranked().enumerate().find(|(_, t)| &t.team == team).map(|(i, _)| i+1)
+    }
+
+    pub fn team_data(&self, team: &TeamKey) -> Option<TeamRankingData> {
+        self.ranking.iter().find(|t| &t.team == team).map(|t| t.ranking.clone())
     }
 
-    pub fn team_data(&self, team: &str) -> Option<TeamRankingData> {
-        self.ranking.iter().find(|t| t.team == team).map(|t| t.ranking.clone())
+}
+
+/// Moves `team` out of `ranking` into `changed`, so its totals can change without breaking the
+/// order of the rest.
+fn take<'c>(ranking: &mut Vec<TeamState>, changed: &'c mut HashMap<TeamKey, TeamState>, team: &TeamKey) -> &'c mut TeamState {
+    changed.entry(team.clone()).or_insert_with(|| match ranking.iter().position(|s| &s.team == team) {
+        Some(i) => ranking.remove(i),
+        None => TeamState::new(team),
+    })
+}
+
+/// What each counted team in a qualification match earned, and whether it was disqualified.
+fn team_results(season: &dyn Season, m: &Match, participation: Participation) -> Vec<TeamResult> {
+    let mut results = Vec::new();
+    for t in m.team_keys().into_iter().flatten() {
+        let color = m.alliance_of(t).unwrap();
+        let alliance = m.alliances.as_ref().unwrap().get(color);
+        if participation.is_counted(alliance, t) {
+            let disqualified = participation.is_disqualified(alliance, t);
+            if let Some(result) = season.result(m, color, disqualified) {
+                results.push((t.clone(), result, disqualified));
+            }
+        }
     }
+    results
+}
 
+/// How one team's rank changed over qualifications.
+#[derive(Debug, Clone, Default)]
+pub struct TeamTrajectory {
+    /// The team's rank after each match in `Trajectory::matches`, or `None` before it had played.
+    pub ranks: Vec<Option<usize>>,
+    /// The team's rank after each match it played in.
+    pub after_own_matches: Vec<usize>,
 }
 
+/// Every team's rank after each qualification match.
+#[derive(Debug, Clone)]
+pub struct Trajectory {
+    /// The qualification matches counted, in order.
+    pub matches: Vec<MatchKey>,
+    pub teams: HashMap<TeamKey, TeamTrajectory>,
+}
+
+/// Counts the qualification matches in `matches` that were played before `time` into rankings
+/// for an event in `year` one at a time, ranking the teams after each one.
+pub fn trajectory(year: i32, matches: &[Match], time: chrono::DateTime<chrono::Utc>, participation: Participation) -> Trajectory {
+    let mut quals: Vec<&Match> = matches.iter()
+        .filter(|m| m.comp_level == the_blue_alliance::matches::CompLevel::QualificationMatch)
+        .collect();
+    quals.sort_unstable_by(|a, b| a.cmp_by_time(b));
+
+    let mut state = EventState::empty(year, participation);
+    let mut counted = Vec::new();
+    let mut ranks: HashMap<TeamKey, Vec<Option<usize>>> = HashMap::new();
+    for m in quals {
+        if !state.update(std::slice::from_ref(m), time) {
+            continue;
+        }
+        counted.push(m.key.clone());
+        for (rank, t) in state.ranked().enumerate() {
+            let team_ranks = ranks.entry(t.team.clone()).or_default();
+            team_ranks.resize(counted.len() - 1, None);
+            team_ranks.push(Some(rank + 1));
+        }
+    }
+
+    Trajectory {
+        teams: state.ranking.into_iter()
+            .map(|t| {
+                let mut team_ranks = ranks.remove(&t.team).unwrap_or_default();
+                team_ranks.resize(counted.len(), None);
+                (t.team, TeamTrajectory { ranks: team_ranks, after_own_matches: t.ranks })
+            })
+            .collect(),
+        matches: counted,
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct TeamState {
-    pub team: String,
+    pub team: TeamKey,
     pub ranking: TeamRankingData,
+    /// The team's rank after each of its own matches, as they were counted. Corrections to
+    /// earlier matches do not rewrite it.
+    #[serde(default)]
+    pub ranks: Vec<usize>,
 }
 
 impl TeamState {
-    fn new(team: &str) -> TeamState {
+    fn new(team: &TeamKey) -> TeamState {
         TeamState {
-            team: team.to_owned(),
-            ranking: TeamRankingData::None,
+            team: team.clone(),
+            ranking: TeamRankingData::default(),
+            ranks: Vec::new(),
+        }
+    }
+}
+
+/// A team's totals toward the rankings, in any season. Teams are ordered by ranking score, then
+/// by each of the season's tiebreakers in turn.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TeamRankingData {
+    pub ranking_score: f64,
+    pub ranking_points: u32,
+    pub score: u32,
+    pub played: u32,
+    /// How many of the played matches the team was disqualified from.
+    pub disqualified: u32,
+    pub tiebreakers: Vec<u32>,
+}
+
+impl TeamRankingData {
+    /// Adds a match to the team's totals. Disqualified teams still play the match, but the season
+    /// decides what they earn for it.
+    pub fn add(&mut self, season: &dyn Season, result: &MatchResult) {
+        self.played += 1;
+        self.ranking_points += result.ranking_points;
+        self.score += result.score;
+        if self.tiebreakers.len() < result.tiebreakers.len() {
+            self.tiebreakers.resize(result.tiebreakers.len(), 0);
         }
+        for (total, value) in self.tiebreakers.iter_mut().zip(result.tiebreakers.iter()) {
+            *total += value;
+        }
+        self.ranking_score = season.ranking_score(self);
     }
 
-    fn add_match(&mut self, m: the_blue_alliance::matches::Match) {
-        self.ranking.accumulate(m, &self.team)
+    /// Takes back a match added with `add`, such as one whose score was corrected since.
+    pub fn remove(&mut self, season: &dyn Season, result: &MatchResult) {
+        self.played -= 1;
+        self.ranking_points -= result.ranking_points;
+        self.score -= result.score;
+        for (total, value) in self.tiebreakers.iter_mut().zip(result.tiebreakers.iter()) {
+            *total -= value;
+        }
+        self.ranking_score = if self.played > 0 { season.ranking_score(self) } else { 0.0 };
     }
 }
 
-#[derive(PartialEq, Eq, Clone)]
-pub enum TeamRankingData {
-    None,
-    S2019(TeamRankingData2019),
+impl PartialEq for TeamRankingData {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
 }
 
+impl Eq for TeamRankingData {}
+
 impl PartialOrd for TeamRankingData {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        match (self, other) {
-            (TeamRankingData::S2019(ref d), TeamRankingData::S2019(ref d2)) => d.partial_cmp(&d2),
-            _ => panic!()
-        }
+        Some(self.cmp(other))
     }
 }
 
-
+/// Ranking scores are compared exactly. Equal averages of whole ranking points always divide to
+/// the same float, and a tolerance would make the order intransitive.
 impl Ord for TeamRankingData {
     fn cmp(&self, other: &Self) -> Ordering {
-        self.partial_cmp(other).unwrap()
+        self.ranking_score.total_cmp(&other.ranking_score)
+            .then_with(|| self.tiebreakers.cmp(&other.tiebreakers))
     }
 }
 
-impl TeamRankingData {
-    fn new() -> TeamRankingData {
-        TeamRankingData::None
-    }
-
-    fn accumulate(&mut self, data: the_blue_alliance::matches::Match, team: &str) {
-        let is_red = if data.alliances.as_ref().unwrap().red.team_keys.contains(&team.to_owned()) {
-            true
-        } else if data.alliances.as_ref().unwrap().blue.team_keys.contains(&team.to_owned()) {
-            false
-        } else {
-            panic!("Match does not contain team!");
-        };
-        
-        match data.score_breakdown.expect("Match does not have score breakdown") {
-            the_blue_alliance::matches::ScoreBreakdown::Year2019(data) => {
-                let data = if is_red {
-                    data.red
-                } else {
-                    data.blue
-                };
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use testing::qual_2019;
 
-                if let TeamRankingData::S2019(ref mut d) = self {
-                    d.played += 1;
-                    d.ranking_points += data.rp as u32;
-                    d.cargo_points += data.cargo_points as u32;
-                    d.panel_points += data.hatch_panel_points as u32;
-                    d.climb_points += data.hab_climb_points as u32;
-                    d.sandstorm_points += data.sand_storm_bonus_points as u32;
-                    d.ranking_score = d.ranking_points as f32 / d.played as f32;
-                } else if let TeamRankingData::None = self {
-                    *self = TeamRankingData::S2019(TeamRankingData2019{
-                        played: 1,
-                        ranking_points: data.rp as u32,
-                        cargo_points: data.cargo_points as u32,
-                        panel_points: data.hatch_panel_points as u32,
-                        climb_points: data.hab_climb_points as u32,
-                        sandstorm_points: data.sand_storm_bonus_points as u32,
-                        ranking_score: data.rp as f32,
-                    });
-                }
-            },
-            _ => panic!("Cannot handle data for this season.")
+    fn matches() -> Vec<Match> {
+        vec![
+            qual_2019(1, [1, 2, 3], [4, 5, 6], [24, 12, 15, 6, 3], [15, 10, 9, 6, 0]),
+            qual_2019(2, [1, 4, 5], [2, 3, 6], [18, 14, 12, 6, 0], [21, 8, 6, 3, 5]),
+            qual_2019(3, [1, 2, 6], [3, 4, 5], [21, 10, 12, 9, 3], [18, 12, 15, 3, 0]),
+        ]
+    }
+
+    fn later() -> chrono::DateTime<chrono::Utc> {
+        chrono::Utc::now()
+    }
+
+    /// Every ranked team's totals, by team number, after checking the ranking is in order.
+    fn summary(state: &EventState) -> Vec<(u32, u32, u32, u32, Vec<u32>)> {
+        assert!(state.ranking.windows(2).all(|w| w[0].ranking >= w[1].ranking));
+        let mut totals: Vec<_> = state.ranked()
+            .map(|t| (t.team.number(), t.ranking.ranking_points, t.ranking.played, t.ranking.disqualified, t.ranking.tiebreakers.clone()))
+            .collect();
+        totals.sort();
+        totals
+    }
+
+    fn fresh(matches: &[Match]) -> EventState {
+        let mut state = EventState::empty(2019, Participation::default());
+        state.update(matches, later());
+        state
+    }
+
+    #[test]
+    fn counts_each_match_once() {
+        let matches = matches();
+        let mut state = EventState::empty(2019, Participation::default());
+        assert!(state.update(&matches[..2], later()));
+        assert!(state.update(&matches, later()));
+        assert!(!state.update(&matches, later()));
+        assert_eq!(summary(&state), summary(&fresh(&matches)));
+        assert_eq!(state.team_data(&TeamKey::from_number(1)).unwrap().played, 3);
+    }
+
+    #[test]
+    fn keeps_each_teams_rank_history() {
+        let mut matches = matches();
+        matches.reverse();
+        for state in &[fresh(&matches), {
+            let mut state = EventState::empty(2019, Participation::default());
+            for i in (0..matches.len()).rev() {
+                state.update(&matches[i..], later());
+            }
+            state
+        }] {
+            for t in &state.ranking {
+                assert_eq!(t.ranks.len(), 3);
+                assert_eq!(t.ranks.last().cloned(), state.rank_of_team(&t.team));
+            }
+            // After the first match its winners share the top three ranks.
+            let first: Vec<usize> = [1, 2, 3].iter().map(|&n| state.ranking.iter().find(|t| t.team.number() == n).unwrap().ranks[0]).collect();
+            assert!(first.iter().all(|&r| r <= 3), "{:?}", first);
         }
     }
-}
 
-#[derive(PartialEq, Clone)]
-pub struct TeamRankingData2019 {
-    pub ranking_score: f32,
-    pub cargo_points: u32,
-    pub panel_points: u32,
-    pub climb_points: u32,
-    pub sandstorm_points: u32,
-    pub played: u32,
-    pub ranking_points: u32,
-}
+    #[test]
+    fn trajectory_follows_the_counted_rankings() {
+        let mut matches = matches();
+        // An unscored match is not counted and gets no column.
+        matches.push(qual_2019(4, [1, 2, 3], [4, 5, 6], [0; 5], [0; 5]));
+        matches[3].score_breakdown = None;
+        let trajectory = trajectory(2019, &matches, later(), Participation::default());
+        let state = fresh(&matches);
 
-impl PartialOrd for TeamRankingData2019 {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        if (self.ranking_score - other.ranking_score).abs() > 0.001 {
-            self.ranking_score.partial_cmp(&other.ranking_score)
-        } else if self.cargo_points != other.cargo_points {
-            Some(self.cargo_points.cmp(&other.cargo_points))
-        } else if self.panel_points != other.panel_points {
-            Some(self.panel_points.cmp(&other.panel_points))
-        } else if self.climb_points != other.climb_points {
-            Some(self.climb_points.cmp(&other.climb_points))
-        } else {
-            Some(self.sandstorm_points.cmp(&other.sandstorm_points))
+        assert_eq!(trajectory.matches, matches[..3].iter().map(|m| m.key.clone()).collect::<Vec<_>>());
+        assert_eq!(trajectory.teams.len(), 6);
+        for (team, tr) in &trajectory.teams {
+            // Every team played every match.
+            assert_eq!(tr.ranks.iter().flatten().cloned().collect::<Vec<_>>(), tr.after_own_matches);
+            // Ties can be ranked either way, but each final rank holds the same totals.
+            let rank = tr.ranks[2].unwrap();
+            assert_eq!(state.team_data(team).unwrap(), state.ranking[rank - 1].ranking);
         }
     }
-}
 
-impl Eq for TeamRankingData2019 {}
+    #[test]
+    fn takes_back_corrected_scores() {
+        let mut matches = matches();
+        let mut state = fresh(&matches);
 
-impl Ord for TeamRankingData2019 {
-    fn cmp(&self, other: &Self) -> Ordering {
-        self.partial_cmp(other).unwrap()
+        // Red's win in the first match is overturned.
+        matches[0] = qual_2019(1, [1, 2, 3], [4, 5, 6], [14, 12, 15, 6, 3], [26, 10, 9, 6, 0]);
+        assert!(state.update(&matches, later()));
+        assert_eq!(summary(&state), summary(&fresh(&matches)));
+        assert_eq!(state.team_data(&TeamKey::from_number(4)).unwrap().ranking_points, 4);
+    }
+
+    #[test]
+    fn takes_back_matches_with_new_disqualifications() {
+        let mut matches = matches();
+        let mut state = fresh(&matches);
+
+        matches[1].alliances.as_mut().unwrap().red.dq_team_keys = Some(vec![TeamKey::from_number(4)]);
+        assert!(state.update(&matches, later()));
+        assert_eq!(summary(&state), summary(&fresh(&matches)));
+        assert_eq!(state.team_data(&TeamKey::from_number(4)).unwrap().disqualified, 1);
+    }
+
+    #[test]
+    fn keeps_teams_whose_only_match_was_taken_back() {
+        let mut matches = matches();
+        matches.push(qual_2019(4, [7, 1, 2], [3, 4, 5], [21, 10, 12, 9, 3], [18, 12, 15, 3, 0]));
+        let mut state = fresh(&matches);
+        let seven = TeamKey::from_number(7);
+        assert_eq!(state.team_data(&seven).unwrap().played, 1);
+
+        // Team 7 turns out to have been a surrogate, which does not count.
+        matches[3].alliances.as_mut().unwrap().red.surrogate_team_keys = Some(vec![seven.clone()]);
+        assert!(state.update(&matches, later()));
+        assert_eq!(summary(&state), summary(&fresh(&matches)));
+        assert_eq!(state.rank_of_team(&seven), None);
+        assert!(state.ranked().all(|t| t.team != seven));
+        let kept = state.ranking.last().unwrap();
+        assert_eq!((&kept.team, kept.ranking.played, kept.ranks.len()), (&seven, 0, 1));
+    }
+
+    #[test]
+    fn saved_states_keep_what_each_match_added() {
+        let mut matches = matches();
+        let saved: EventState = serde_cbor::from_slice(&serde_cbor::to_vec(&fresh(&matches)).unwrap()).unwrap();
+        let mut state = saved;
+
+        matches[2] = qual_2019(3, [1, 2, 6], [3, 4, 5], [21, 10, 12, 9, 3], [28, 12, 15, 3, 0]);
+        assert!(state.update(&matches, later()));
+        assert_eq!(summary(&state), summary(&fresh(&matches)));
     }
-}
\ No newline at end of file
+}
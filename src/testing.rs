@@ -0,0 +1,114 @@
+//! Matches for unit tests, built the way TBA sends them.
+
+use the_blue_alliance::keys::TeamKey;
+use the_blue_alliance::matches::Match;
+use opr::{ComponentTable, Estimate};
+
+fn team_keys(numbers: &[u32]) -> Vec<String> {
+    numbers.iter().map(|n| format!("frc{}", n)).collect()
+}
+
+/// Qualification match `number` at the event `key`, between the teams numbered in `red` and
+/// `blue`. A score of -1 means the match has not been played. Matches are played ten minutes
+/// apart from `time`, in Unix seconds.
+pub fn qual_at(key: &str, number: u32, red: [u32; 3], blue: [u32; 3], scores: (i32, i32), time: u64) -> Match {
+    let winner = if scores.0 < 0 || scores.0 == scores.1 {
+        ""
+    } else if scores.0 > scores.1 {
+        "red"
+    } else {
+        "blue"
+    };
+    let played = scores.0 >= 0;
+    serde_json::from_value(serde_json::json!({
+        "key": format!("{}_qm{}", key, number),
+        "comp_level": "qm",
+        "set_number": 1,
+        "match_number": number,
+        "event_key": key,
+        "alliances": {
+            "red": { "score": scores.0, "team_keys": team_keys(&red), "surrogate_team_keys": [], "dq_team_keys": [] },
+            "blue": { "score": scores.1, "team_keys": team_keys(&blue), "surrogate_team_keys": [], "dq_team_keys": [] },
+        },
+        "winning_alliance": winner,
+        "time": time + 600 * u64::from(number),
+        "actual_time": if played { Some(time + 600 * u64::from(number)) } else { None },
+    })).unwrap()
+}
+
+/// Qualification match `number` at `2019test`.
+pub fn qual(number: u32, red: [u32; 3], blue: [u32; 3], scores: (i32, i32)) -> Match {
+    qual_at("2019test", number, red, blue, scores, 1_554_000_000)
+}
+
+/// A played qualification match at `<year>test`, for a season since 2020 whose breakdowns report
+/// each alliance's ranking points as `rp`.
+pub fn qual_with_rp(year: i32, number: u32, red: [u32; 3], blue: [u32; 3], scores: (i32, i32), rp: (i32, i32)) -> Match {
+    let mut value = serde_json::to_value(qual_at(&format!("{}test", year), number, red, blue, scores, 1_700_000_000)).unwrap();
+    value["score_breakdown"] = serde_json::json!({
+        "red": { "totalPoints": scores.0, "rp": rp.0 },
+        "blue": { "totalPoints": scores.1, "rp": rp.1 },
+    });
+    serde_json::from_value(value).unwrap()
+}
+
+/// One alliance's 2019 breakdown from its `[cargo, panel, climb, sandstorm, fouls]` points,
+/// without ranking point bonuses.
+fn breakdown_2019(points: [i32; 5], rp: i32) -> serde_json::Value {
+    let mut breakdown = serde_json::json!({
+        "adjustPoints": 0,
+        "autoPoints": points[3],
+        "cargoPoints": points[0],
+        "hatchPanelPoints": points[1],
+        "habClimbPoints": points[2],
+        "sandStormBonusPoints": points[3],
+        "foulPoints": points[4],
+        "teleopPoints": points[0] + points[1] + points[2],
+        "totalPoints": points.iter().sum::<i32>(),
+        "completeRocketRankingPoint": false,
+        "completedRocketFar": false,
+        "completedRocketNear": false,
+        "habDockingRankingPoint": false,
+        "foulCount": 0,
+        "techFoulCount": 0,
+        "rp": rp,
+    });
+    let strings = [
+        "bay1", "bay2", "bay3", "bay4", "bay5", "bay6", "bay7", "bay8",
+        "endgameRobot1", "endgameRobot2", "endgameRobot3", "habLineRobot1", "habLineRobot2", "habLineRobot3",
+        "lowLeftRocketFar", "lowLeftRocketNear", "lowRightRocketFar", "lowRightRocketNear",
+        "midLeftRocketFar", "midLeftRocketNear", "midRightRocketFar", "midRightRocketNear",
+        "preMatchBay1", "preMatchBay2", "preMatchBay3", "preMatchBay6", "preMatchBay7", "preMatchBay8",
+        "preMatchLevelRobot1", "preMatchLevelRobot2", "preMatchLevelRobot3",
+        "topLeftRocketFar", "topLeftRocketNear", "topRightRocketFar", "topRightRocketNear",
+    ];
+    for s in strings.iter() {
+        breakdown[*s] = serde_json::json!("None");
+    }
+    breakdown
+}
+
+/// A played 2019 qualification match at `2019test`, scored from each alliance's
+/// `[cargo, panel, climb, sandstorm, fouls]` points.
+pub fn qual_2019(number: u32, red: [u32; 3], blue: [u32; 3], red_points: [i32; 5], blue_points: [i32; 5]) -> Match {
+    let scores = (red_points.iter().sum::<i32>(), blue_points.iter().sum::<i32>());
+    let rp = |a: i32, b: i32| if a > b { 2 } else if a == b { 1 } else { 0 };
+    let mut value = serde_json::to_value(qual(number, red, blue, scores)).unwrap();
+    value["score_breakdown"] = serde_json::json!({
+        "red": breakdown_2019(red_points, rp(scores.0, scores.1)),
+        "blue": breakdown_2019(blue_points, rp(scores.1, scores.0)),
+    });
+    serde_json::from_value(value).unwrap()
+}
+
+/// A component table with `columns`, giving each team numbered in `rows` its values, without
+/// standard errors or fit statistics.
+pub fn oprs(columns: Vec<&'static str>, rows: &[(u32, &[f64])]) -> ComponentTable {
+    ComponentTable {
+        stats: vec![None; columns.len()],
+        columns,
+        values: rows.iter()
+            .map(|&(n, values)| (TeamKey::from_number(n), values.iter().map(|&value| Estimate { value, standard_error: f64::NAN }).collect()))
+            .collect(),
+    }
+}
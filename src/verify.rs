@@ -0,0 +1,190 @@
+use std::collections::{HashMap, HashSet};
+use the_blue_alliance::event::{EventRanking, EventRankings};
+use the_blue_alliance::keys::TeamKey;
+use state::{EventState, TeamState};
+
+/// One value that differs between the official rankings and ours.
+#[derive(Debug, Clone)]
+pub struct Difference {
+    pub name: String,
+    pub official: f64,
+    pub computed: f64,
+    /// How many decimal places the official value is given to.
+    pub precision: usize,
+}
+
+/// How a team's official ranking compares to the one computed from match results. A team missing
+/// from either side has no rank there and no other differences.
+#[derive(Debug, Clone)]
+pub struct Comparison {
+    pub team: TeamKey,
+    pub official_rank: Option<usize>,
+    pub computed_rank: Option<usize>,
+    pub differences: Vec<Difference>,
+}
+
+impl Comparison {
+    pub fn agrees(&self) -> bool {
+        self.official_rank == self.computed_rank && self.differences.is_empty()
+    }
+}
+
+/// Whether `official`, given to `precision` decimal places, could be a rounding of `computed`.
+fn rounds_to(official: f64, computed: f64, precision: usize) -> bool {
+    (official - computed).abs() <= 0.5 * 10f64.powi(-(precision as i32)) + 1e-6
+}
+
+/// Every value that differs between a team's official ranking and ours. Only the sort orders
+/// the season follows the game manual for are checked.
+fn differences(official: &EventRanking, computed: &TeamState, state: &EventState, precisions: &[usize]) -> Vec<Difference> {
+    let mut values = vec![
+        ("Played".to_owned(), official.matches_played, computed.ranking.played),
+        ("DQs".to_owned(), official.dq, computed.ranking.disqualified),
+    ].into_iter()
+        .filter_map(|(name, official, computed)| Some(Difference {
+            name,
+            official: f64::from(official?),
+            computed: f64::from(computed),
+            precision: 0,
+        }))
+        .collect::<Vec<_>>();
+
+    let season = state.season();
+    let names = std::iter::once(season.ranking_score_name()).chain(season.tiebreakers().iter().cloned());
+    let ours = std::iter::once(computed.ranking.ranking_score).chain(computed.ranking.tiebreakers.iter().map(|&v| f64::from(v)));
+    let theirs = official.sort_orders.iter().flatten().enumerate();
+    values.extend(names.zip(ours).zip(theirs).take(season.checked_sort_orders()).map(|((name, computed), (i, &official))| Difference {
+        name: name.to_owned(),
+        official,
+        computed,
+        precision: precisions.get(i).cloned().unwrap_or(2),
+    }));
+
+    values.retain(|d| !rounds_to(d.official, d.computed, d.precision));
+    values
+}
+
+/// Compares every team in TBA's official rankings with the rankings in `state`, in official
+/// order, followed by any teams only we ranked.
+pub fn compare(official: &EventRankings, state: &EventState) -> Vec<Comparison> {
+    let computed: HashMap<&TeamKey, (usize, &TeamState)> = state.ranked()
+        .enumerate()
+        .map(|(i, t)| (&t.team, (i + 1, t)))
+        .collect();
+
+    let precisions: Vec<usize> = official.sort_order_info.iter().map(|i| i.precision.max(0) as usize).collect();
+
+    let mut ranked: Vec<&EventRanking> = official.rankings.iter().collect();
+    ranked.sort_by_key(|r| r.rank);
+
+    let mut comparisons: Vec<Comparison> = ranked.iter()
+        .map(|r| {
+            let ours = computed.get(&r.team_key);
+            Comparison {
+                team: r.team_key.clone(),
+                official_rank: Some(r.rank as usize),
+                computed_rank: ours.map(|&(rank, _)| rank),
+                differences: ours.map(|&(_, t)| differences(r, t, state, &precisions)).unwrap_or_default(),
+            }
+        })
+        .collect();
+
+    let official_teams: HashSet<&TeamKey> = official.rankings.iter().map(|r| &r.team_key).collect();
+    comparisons.extend(state.ranked()
+        .enumerate()
+        .filter(|(_, t)| !official_teams.contains(&t.team))
+        .map(|(i, t)| Comparison {
+            team: t.team.clone(),
+            official_rank: None,
+            computed_rank: Some(i + 1),
+            differences: Vec::new(),
+        }));
+    comparisons
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use the_blue_alliance::event::SortOrderInfo;
+    use state::Participation;
+    use testing::{qual_2019, qual_with_rp};
+
+    fn counted(year: i32, matches: &[the_blue_alliance::matches::Match]) -> EventState {
+        let mut state = EventState::empty(year, Participation::default());
+        state.update(matches, chrono::Utc::now());
+        state
+    }
+
+    fn state_2019() -> EventState {
+        counted(2019, &[
+            qual_2019(1, [1, 2, 3], [4, 5, 6], [24, 12, 15, 6, 3], [15, 10, 9, 6, 0]),
+            qual_2019(2, [1, 4, 5], [2, 3, 6], [18, 14, 12, 6, 0], [21, 8, 6, 3, 5]),
+        ])
+    }
+
+    /// Official rankings of `(team, rank, sort orders)`, with the ranking score to two places
+    /// and every tiebreaker to none.
+    fn official(rankings: Vec<(u32, i32, Vec<f64>)>) -> EventRankings {
+        EventRankings {
+            sort_order_info: (0..5).map(|i| SortOrderInfo { name: format!("Sort {}", i), precision: if i == 0 { 2 } else { 0 } }).collect(),
+            rankings: rankings.into_iter()
+                .map(|(team, rank, sort_orders)| EventRanking {
+                    rank,
+                    team_key: TeamKey::from_number(team),
+                    matches_played: None,
+                    dq: None,
+                    sort_orders: Some(sort_orders),
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn values_round_to_the_official_precision() {
+        assert!(rounds_to(1.23, 1.234, 2));
+        assert!(rounds_to(1.24, 1.235, 2));
+        assert!(!rounds_to(1.23, 1.236, 2));
+        assert!(rounds_to(3.0, 3.4, 0));
+        assert!(!rounds_to(3.0, 3.6, 0));
+        assert!(rounds_to(1.3333, 4.0 / 3.0, 4));
+    }
+
+    #[test]
+    fn only_checked_sort_orders_are_compared() {
+        let data = |state: &EventState, n| state.team_data(&TeamKey::from_number(n)).unwrap();
+
+        // 2019 follows the manual for the ranking score and every tiebreaker.
+        let state = state_2019();
+        let ours = data(&state, 1);
+        let mut sort_orders: Vec<f64> = std::iter::once(ours.ranking_score).chain(ours.tiebreakers.iter().map(|&v| f64::from(v))).collect();
+        sort_orders[4] += 1.0;
+        let comparison = &compare(&official(vec![(1, 1, sort_orders)]), &state)[0];
+        assert_eq!(comparison.differences.iter().map(|d| d.name.as_str()).collect::<Vec<_>>(), ["Sandstorm"]);
+
+        // Later seasons only check the ranking score, since their tiebreakers are approximated.
+        let state = counted(2023, &[qual_with_rp(2023, 1, [1, 2, 3], [4, 5, 6], (80, 60), (3, 0))]);
+        let ours = data(&state, 1);
+        let comparison = &compare(&official(vec![(1, 1, vec![ours.ranking_score, 999.0])]), &state)[0];
+        assert!(comparison.differences.is_empty());
+        let comparison = &compare(&official(vec![(1, 1, vec![ours.ranking_score + 1.0, 999.0])]), &state)[0];
+        assert_eq!(comparison.differences.iter().map(|d| d.name.as_str()).collect::<Vec<_>>(), ["RS"]);
+    }
+
+    #[test]
+    fn teams_on_one_side_follow_the_official_order() {
+        let state = state_2019();
+        let comparisons = compare(&official(vec![(9, 3, vec![]), (2, 2, vec![]), (1, 1, vec![])]), &state);
+
+        let teams: Vec<u32> = comparisons.iter().map(|c| c.team.number()).collect();
+        assert_eq!(teams[..3], [1, 2, 9]);
+        assert_eq!(comparisons[2].computed_rank, None);
+        assert!(comparisons[2].differences.is_empty());
+
+        // Teams only we ranked come last, in our order.
+        let ours: Vec<&Comparison> = comparisons[3..].iter().collect();
+        assert_eq!(ours.len(), 4);
+        assert!(ours.iter().all(|c| c.official_rank.is_none()));
+        assert!(ours.windows(2).all(|w| w[0].computed_rank < w[1].computed_rank));
+        assert!(!ours.iter().any(|c| [1, 2, 9].contains(&c.team.number())));
+    }
+}
@@ -0,0 +1,31 @@
+use crate::TBA;
+use futures::future;
+use crate::Error;
+use crate::keys::{EventKey, TeamKey};
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AwardRecipient {
+    pub team_key: Option<TeamKey>,
+    /// The person who won an individual award, like Dean's List.
+    pub awardee: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Award {
+    pub name: String,
+    pub award_type: i32,
+    pub event_key: EventKey,
+    pub recipient_list: Vec<AwardRecipient>,
+    pub year: i32,
+}
+
+impl Award {
+    pub fn for_team_key_in_year(tba: &TBA, team_key: &TeamKey, year: i32) -> impl future::Future<Error = Error, Item = Vec<Award>> + Send{
+        assert_eq!(year.to_string().len(), 4);
+        tba.get("/team/".to_owned() + team_key.as_str() + "/awards/" + &year.to_string())
+    }
+
+    pub fn at_event(tba: &TBA, event_key: &EventKey) -> impl future::Future<Error = Error, Item = Vec<Award>> + Send{
+        tba.get("/event/".to_owned() + event_key.as_str() + "/awards")
+    }
+}
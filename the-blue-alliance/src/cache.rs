@@ -1,6 +1,7 @@
-use crate::team::Team;
+use crate::team::{Team, TeamEventStatus};
 use crate::district::District;
-use crate::event::Event;
+use crate::award::Award;
+use crate::event::{Event, EliminationAlliance, EventRankings};
 use crate::matches::Match;
 use ::chrono::{DateTime, Local};
 use std::collections::HashMap;
@@ -8,6 +9,7 @@ use serde_cbor;
 use std::fs::File;
 use time::Duration;
 use chrono;
+use crate::keys::{EventKey, TeamKey};
 #[derive(Serialize, Deserialize, Clone)]
 pub enum CachedData {
     Team(Box<Team>),
@@ -19,7 +21,12 @@ pub enum CachedData {
     Match(Box<Match>),
     Matches(Vec<Match>),
     Years(Vec<u32>),
-    Keys(Vec<String>),
+    TeamKeys(Vec<TeamKey>),
+    EventKeys(Vec<EventKey>),
+    Alliances(Option<Vec<EliminationAlliance>>),
+    Rankings(Option<EventRankings>),
+    Awards(Vec<Award>),
+    TeamEventStatuses(HashMap<EventKey, Option<TeamEventStatus>>),
 }
 
 pub trait ToInternal<T> {
@@ -107,10 +114,55 @@ impl ToInternal<Vec<u32>> for CachedData {
     }
 }
 
-impl ToInternal<Vec<String>> for CachedData {
-    fn into_internal(self) -> Vec<String> {
+impl ToInternal<Vec<TeamKey>> for CachedData {
+    fn into_internal(self) -> Vec<TeamKey> {
         match self {
-            CachedData::Keys(t) => t,
+            CachedData::TeamKeys(t) => t,
+            _ => panic!(),
+        }
+    }
+}
+
+impl ToInternal<Vec<EventKey>> for CachedData {
+    fn into_internal(self) -> Vec<EventKey> {
+        match self {
+            CachedData::EventKeys(t) => t,
+            _ => panic!(),
+        }
+    }
+}
+
+impl ToInternal<Option<Vec<EliminationAlliance>>> for CachedData {
+    fn into_internal(self) -> Option<Vec<EliminationAlliance>> {
+        match self {
+            CachedData::Alliances(t) => t,
+            _ => panic!(),
+        }
+    }
+}
+
+impl ToInternal<Option<EventRankings>> for CachedData {
+    fn into_internal(self) -> Option<EventRankings> {
+        match self {
+            CachedData::Rankings(t) => t,
+            _ => panic!(),
+        }
+    }
+}
+
+impl ToInternal<Vec<Award>> for CachedData {
+    fn into_internal(self) -> Vec<Award> {
+        match self {
+            CachedData::Awards(t) => t,
+            _ => panic!(),
+        }
+    }
+}
+
+impl ToInternal<HashMap<EventKey, Option<TeamEventStatus>>> for CachedData {
+    fn into_internal(self) -> HashMap<EventKey, Option<TeamEventStatus>> {
+        match self {
+            CachedData::TeamEventStatuses(t) => t,
             _ => panic!(),
         }
     }
@@ -140,8 +192,14 @@ impl CachedDataTimed {
     }
 }
 
+/// Bumped whenever `CachedData` or any type cached in it changes shape, so that an older cache
+/// file is discarded instead of failing to load.
+const CACHE_VERSION: u32 = 3;
+
 #[derive(Serialize, Deserialize)]
 pub struct CacheStore {
+    #[serde(default)]
+    version: u32,
     store: HashMap<String, CachedDataTimed>
 }
 
@@ -153,21 +211,24 @@ impl CacheStore {
                 warn!("Cannot load cache file: {}", e);
                 None
             },
-            Ok(file) => match serde_cbor::from_reader(file) {
+            Ok(file) => match serde_cbor::from_reader::<CacheStore, _>(file) {
+                Ok(ref v) if v.version != CACHE_VERSION => {
+                    info!("Discarding cache from an older version.");
+                    None
+                },
                 Ok(v) => {
                     info!("Cache loaded.");
-                    v
+                    Some(v)
                 },
                 Err(e) => {
-                    warn!("Cannot deserialize cache data: {}", e);
+                    warn!("Cannot deserialize cache data, discarding it: {}", e);
                     None
                 },
             },
-        }.or_else(|| {
-            Some(CacheStore {
-                store: HashMap::new(),
-            })
-        }).unwrap()
+        }.unwrap_or_else(|| CacheStore {
+            version: CACHE_VERSION,
+            store: HashMap::new(),
+        })
     }
 
     pub fn cache<C: ToCache>(&mut self, query: String, data: C, last_modified: String, expires: Duration) {
@@ -252,8 +313,38 @@ impl ToCache for Vec<u32> {
     }
 }
 
-impl ToCache for Vec<String> {
+impl ToCache for Vec<TeamKey> {
+    fn cache(self) -> CachedData {
+        CachedData::TeamKeys(self)
+    }
+}
+
+impl ToCache for Vec<EventKey> {
+    fn cache(self) -> CachedData {
+        CachedData::EventKeys(self)
+    }
+}
+
+impl ToCache for Option<Vec<EliminationAlliance>> {
     fn cache(self) -> CachedData {
-        CachedData::Keys(self)
+        CachedData::Alliances(self)
     }
-}
\ No newline at end of file
+}
+
+impl ToCache for Option<EventRankings> {
+    fn cache(self) -> CachedData {
+        CachedData::Rankings(self)
+    }
+}
+
+impl ToCache for Vec<Award> {
+    fn cache(self) -> CachedData {
+        CachedData::Awards(self)
+    }
+}
+
+impl ToCache for HashMap<EventKey, Option<TeamEventStatus>> {
+    fn cache(self) -> CachedData {
+        CachedData::TeamEventStatuses(self)
+    }
+}
@@ -3,6 +3,7 @@ use crate::team::Team;
 use crate::event::Event;
 use futures::future;
 use crate::Error;
+use crate::keys::{EventKey, TeamKey};
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct District {
@@ -18,8 +19,8 @@ impl District {
         tba.get("/districts/".to_owned() + &year.to_string())
     }
 
-    pub fn for_team_key(tba: &TBA, team_key: &str) -> impl future::Future<Error = Error, Item = Vec<District>> + Send{
-        tba.get("/team/".to_owned() + team_key + "/districts")
+    pub fn for_team_key(tba: &TBA, team_key: &TeamKey) -> impl future::Future<Error = Error, Item = Vec<District>> + Send{
+        tba.get("/team/".to_owned() + team_key.as_str() + "/districts")
     }
 
     pub fn teams(&self, tba: &TBA) -> impl future::Future<Error = Error, Item = Vec<Team>> + Send{
@@ -30,7 +31,7 @@ impl District {
 //        tba.get("/district/".to_owned() + &self.key + "/teams/simple")
 //    }
 
-    pub fn team_keys(&self, tba: &TBA) -> impl future::Future<Error = Error, Item = Vec<String>> + Send{
+    pub fn team_keys(&self, tba: &TBA) -> impl future::Future<Error = Error, Item = Vec<TeamKey>> + Send{
         tba.get("/district/".to_owned() + &self.key + "/teams/keys")
     }
 
@@ -42,7 +43,7 @@ impl District {
 //        tba.get("/district/".to_owned() + &self.key + "/events/simple")
 //    }
 
-    pub fn event_keys(&self, tba: &TBA) -> impl future::Future<Error = Error, Item = Vec<String>> + Send{
+    pub fn event_keys(&self, tba: &TBA) -> impl future::Future<Error = Error, Item = Vec<EventKey>> + Send{
         tba.get("/district/".to_owned() + &self.key + "/events/keys")
     }
 }
\ No newline at end of file
@@ -4,8 +4,10 @@ use crate::district::District;
 use crate::team::Team;
 use crate::matches::Match;
 use chrono::NaiveDate;
+use chrono_tz::Tz;
 use futures::future;
 use crate::Error;
+use crate::keys::{EventKey, TeamKey};
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum WebcastType{
@@ -37,7 +39,7 @@ pub struct Webcast {
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Event {
-    pub key: String,
+    pub key: EventKey,
     pub name: String,
     pub event_code: String,
     pub event_type: i32,
@@ -63,19 +65,24 @@ pub struct Event {
     pub first_event_id: Option<String>,
     pub first_event_code: Option<String>,
     pub webcasts: Option<Vec<Webcast>>,
-    pub division_keys: Option<Vec<String>>,
-    pub parent_event_key: Option<String>,
+    pub division_keys: Option<Vec<EventKey>>,
+    pub parent_event_key: Option<EventKey>,
     pub playoff_type: Option<i32>,
     pub playoff_type_string: Option<String>
 }
 
 impl Event {
-    pub fn from_key(tba: &TBA, key: &str) -> impl future::Future<Error = Error, Item = Box<Event>> + Send{
-        tba.get("/event/".to_owned() + key)
+    pub fn from_key(tba: &TBA, key: &EventKey) -> impl future::Future<Error = Error, Item = Box<Event>> + Send{
+        tba.get("/event/".to_owned() + key.as_str())
     }
 
-    pub fn for_team_key(tba: &TBA, team_key: &str) -> impl future::Future<Error = Error, Item = Vec<Event>> + Send{
-        tba.get("/team/".to_owned() + team_key + "/events")
+    pub fn for_team_key(tba: &TBA, team_key: &TeamKey) -> impl future::Future<Error = Error, Item = Vec<Event>> + Send{
+        tba.get("/team/".to_owned() + team_key.as_str() + "/events")
+    }
+
+    pub fn for_team_key_in_year(tba: &TBA, team_key: &TeamKey, year: i32) -> impl future::Future<Error = Error, Item = Vec<Event>> + Send{
+        assert_eq!(year.to_string().len(), 4);
+        tba.get("/team/".to_owned() + team_key.as_str() + "/events/" + &year.to_string())
     }
 
     pub fn in_year(tba: &TBA, year: i32) -> impl future::Future<Error = Error, Item = Vec<Event>> + Send{
@@ -83,12 +90,95 @@ impl Event {
         tba.get("/events/".to_owned() + &year.to_string())
     }
 
+    /// Whether this is a season event (regional, district or championship), rather than an
+    /// offseason or preseason event.
+    pub fn is_official(&self) -> bool {
+        matches!(self.event_type, 0..=5 | 7)
+    }
+
+    /// The time zone the event is held in, or UTC if TBA does not know it.
+    pub fn time_zone(&self) -> Tz {
+        self.timezone.as_ref()
+            .and_then(|tz| tz.parse().ok())
+            .unwrap_or(Tz::UTC)
+    }
+
     pub fn teams(&self, tba: &TBA) -> impl future::Future<Error = Error, Item = Vec<Team>> + Send{
-        tba.get("/event/".to_owned() + &self.key + "/teams")
+        tba.get("/event/".to_owned() + self.key.as_str() + "/teams")
     }
 
     pub fn matches(&self, tba: &TBA) -> impl future::Future<Error = Error, Item = Vec<Match>> + Send{
-        tba.get("/event/".to_owned() + &self.key + "/matches")
+        tba.get("/event/".to_owned() + self.key.as_str() + "/matches")
+    }
+
+    /// The playoff alliances in seed order, or `None` before alliance selection.
+    pub fn alliances(&self, tba: &TBA) -> impl future::Future<Error = Error, Item = Option<Vec<EliminationAlliance>>> + Send{
+        tba.get("/event/".to_owned() + self.key.as_str() + "/alliances")
+    }
+
+    /// The official qualification rankings, or `None` before any have been published.
+    pub fn rankings(&self, tba: &TBA) -> impl future::Future<Error = Error, Item = Option<EventRankings>> + Send{
+        tba.get("/event/".to_owned() + self.key.as_str() + "/rankings")
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EventRankings {
+    pub rankings: Vec<EventRanking>,
+    /// The name and display precision of each value in `EventRanking::sort_orders`.
+    pub sort_order_info: Vec<SortOrderInfo>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EventRanking {
+    pub rank: i32,
+    pub team_key: TeamKey,
+    pub matches_played: Option<i32>,
+    /// How many matches the team was disqualified from.
+    pub dq: Option<i32>,
+    /// The values teams are sorted by, most important first.
+    pub sort_orders: Option<Vec<f64>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SortOrderInfo {
+    pub name: String,
+    pub precision: i32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AllianceBackup {
+    #[serde(rename = "in")]
+    pub team_in: TeamKey,
+    #[serde(rename = "out")]
+    pub team_out: TeamKey,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EliminationAlliance {
+    pub name: Option<String>,
+    pub backup: Option<AllianceBackup>,
+    pub declines: Option<Vec<TeamKey>>,
+    /// The captain, then each pick in order.
+    pub picks: Vec<TeamKey>,
+    pub status: Option<AllianceStatus>,
+}
+
+/// How far an alliance has made it through the playoffs.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AllianceStatus {
+    /// The comp level of the alliance's last series, like `sf` or `f`.
+    pub level: Option<String>,
+    /// `won`, `eliminated` or `playing`.
+    pub status: Option<String>,
+    /// The round of a double elimination bracket, like `Round 3` or `Finals`.
+    pub double_elim_round: Option<String>,
+}
+
+impl EliminationAlliance {
+    /// Every team that can play for the alliance, including a backup robot that was called in.
+    pub fn teams(&self) -> Vec<&TeamKey> {
+        self.picks.iter().chain(self.backup.as_ref().map(|b| &b.team_in)).collect()
     }
 }
 
@@ -114,7 +204,7 @@ impl Ord for Event {
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct EventSimple {
-    pub key: String,
+    pub key: EventKey,
     pub name: String,
     pub event_code: String,
     pub event_type: i32,
@@ -0,0 +1,349 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+use serde::{Serialize, Serializer, Deserialize, Deserializer};
+use serde::de;
+use crate::matches::CompLevel;
+
+/// Returned when a string is not a valid TBA key of the requested kind.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseKeyError {
+    kind: &'static str,
+    key: String,
+}
+
+impl ParseKeyError {
+    fn new(kind: &'static str, key: &str) -> ParseKeyError {
+        ParseKeyError {
+            kind,
+            key: key.to_owned(),
+        }
+    }
+}
+
+impl fmt::Display for ParseKeyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "\"{}\" is not a valid {} key", self.key, self.kind)
+    }
+}
+
+impl std::error::Error for ParseKeyError {}
+
+/// A team key such as `frc254`.
+///
+/// Parsing accepts a bare team number (`254`) and is case insensitive. Offseason "B" robots
+/// keep their letter suffix (`frc254B`).
+///
+/// Keys from TBA that do not parse, like the `frc0` placeholder some offseason events use, are
+/// kept as they are with team number 0, so one odd key does not lose a whole match list.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TeamKey {
+    key: String,
+    number: u32,
+}
+
+impl TeamKey {
+    pub fn from_number(number: u32) -> TeamKey {
+        TeamKey {
+            key: format!("frc{}", number),
+            number,
+        }
+    }
+
+    pub fn number(&self) -> u32 {
+        self.number
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.key
+    }
+}
+
+impl FromStr for TeamKey {
+    type Err = ParseKeyError;
+
+    fn from_str(s: &str) -> Result<TeamKey, ParseKeyError> {
+        let trimmed = s.trim();
+        let rest = if trimmed.len() > 3 && trimmed[..3].eq_ignore_ascii_case("frc") {
+            &trimmed[3..]
+        } else {
+            trimmed
+        };
+        let digits = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+        let (number, suffix) = rest.split_at(digits);
+        let number: u32 = number.parse().map_err(|_| ParseKeyError::new("team", s))?;
+
+        if number == 0 || suffix.len() > 1 || !suffix.chars().all(|c| c.is_ascii_alphabetic()) {
+            return Err(ParseKeyError::new("team", s));
+        }
+
+        Ok(TeamKey {
+            key: format!("frc{}{}", number, suffix.to_ascii_uppercase()),
+            number,
+        })
+    }
+}
+
+impl PartialOrd for TeamKey {
+    fn partial_cmp(&self, other: &TeamKey) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for TeamKey {
+    /// Orders numerically, so that `frc254` comes before `frc1114`.
+    fn cmp(&self, other: &TeamKey) -> Ordering {
+        self.number.cmp(&other.number).then_with(|| self.key.cmp(&other.key))
+    }
+}
+
+/// An event key such as `2019miket`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct EventKey {
+    key: String,
+}
+
+impl EventKey {
+    pub fn year(&self) -> i32 {
+        self.key[..4].parse().expect("EventKey was not validated")
+    }
+
+    /// The part of the key after the year, e.g. `miket`.
+    pub fn event_code(&self) -> &str {
+        &self.key[4..]
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.key
+    }
+}
+
+impl FromStr for EventKey {
+    type Err = ParseKeyError;
+
+    fn from_str(s: &str) -> Result<EventKey, ParseKeyError> {
+        let key = s.trim().to_ascii_lowercase();
+        if key.len() > 4
+            && key.is_char_boundary(4)
+            && key[..4].chars().all(|c| c.is_ascii_digit())
+            && key[4..].chars().all(|c| c.is_ascii_alphanumeric()) {
+            Ok(EventKey { key })
+        } else {
+            Err(ParseKeyError::new("event", s))
+        }
+    }
+}
+
+/// A match key such as `2019miket_qm12` or `2019miket_sf2m1`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MatchKey {
+    key: String,
+    event: EventKey,
+    comp_level: CompLevel,
+    set_number: u32,
+    match_number: u32,
+}
+
+impl MatchKey {
+    pub fn event(&self) -> &EventKey {
+        &self.event
+    }
+
+    pub fn year(&self) -> i32 {
+        self.event.year()
+    }
+
+    pub fn comp_level(&self) -> CompLevel {
+        self.comp_level
+    }
+
+    /// The set number. Qualification matches are always in set 1.
+    pub fn set_number(&self) -> u32 {
+        self.set_number
+    }
+
+    pub fn match_number(&self) -> u32 {
+        self.match_number
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.key
+    }
+}
+
+impl FromStr for MatchKey {
+    type Err = ParseKeyError;
+
+    fn from_str(s: &str) -> Result<MatchKey, ParseKeyError> {
+        let err = || ParseKeyError::new("match", s);
+        let lower = s.trim().to_ascii_lowercase();
+        let mut parts = lower.splitn(2, '_');
+        let event: EventKey = parts.next().ok_or_else(err)?.parse().map_err(|_| err())?;
+        let rest = parts.next().ok_or_else(err)?;
+
+        let level_len = rest.find(|c: char| c.is_ascii_digit()).ok_or_else(err)?;
+        let comp_level = match &rest[..level_len] {
+            "qm" => CompLevel::QualificationMatch,
+            "ef" => CompLevel::EighthFinal,
+            "qf" => CompLevel::QuarterFinal,
+            "sf" => CompLevel::SemiFinal,
+            "f" => CompLevel::Final,
+            _ => return Err(err()),
+        };
+        let numbers = &rest[level_len..];
+
+        let (set_number, match_number) = if comp_level == CompLevel::QualificationMatch {
+            (1, numbers.parse().map_err(|_| err())?)
+        } else {
+            let mut numbers = numbers.splitn(2, 'm');
+            let set = numbers.next().ok_or_else(err)?.parse().map_err(|_| err())?;
+            let number = numbers.next().ok_or_else(err)?.parse().map_err(|_| err())?;
+            (set, number)
+        };
+
+        Ok(MatchKey {
+            key: lower,
+            event,
+            comp_level,
+            set_number,
+            match_number,
+        })
+    }
+}
+
+macro_rules! key_impls {
+    ($key:ident) => {
+        impl fmt::Display for $key {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str(&self.key)
+            }
+        }
+
+        impl AsRef<str> for $key {
+            fn as_ref(&self) -> &str {
+                &self.key
+            }
+        }
+
+        impl PartialEq<str> for $key {
+            fn eq(&self, other: &str) -> bool {
+                self.key == other
+            }
+        }
+
+        impl<'a> PartialEq<&'a str> for $key {
+            fn eq(&self, other: &&'a str) -> bool {
+                self.key == *other
+            }
+        }
+
+        impl Serialize for $key {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.serialize_str(&self.key)
+            }
+        }
+
+    };
+}
+
+macro_rules! strict_deserialize {
+    ($key:ident) => {
+        impl<'de> Deserialize<'de> for $key {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<$key, D::Error> {
+                let s = String::deserialize(deserializer)?;
+                s.parse().map_err(de::Error::custom)
+            }
+        }
+    };
+}
+
+key_impls!(TeamKey);
+key_impls!(EventKey);
+key_impls!(MatchKey);
+strict_deserialize!(EventKey);
+strict_deserialize!(MatchKey);
+
+impl<'de> Deserialize<'de> for TeamKey {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<TeamKey, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        Ok(s.parse().unwrap_or(TeamKey { key: s, number: 0 }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn team_key_parses_numbers_and_prefixes() {
+        let key: TeamKey = "frc254".parse().unwrap();
+        assert_eq!(key.number(), 254);
+        assert_eq!(key.as_str(), "frc254");
+        assert_eq!("254".parse::<TeamKey>().unwrap(), key);
+        assert_eq!(" FRC254 ".parse::<TeamKey>().unwrap(), key);
+        assert_eq!("frc254b".parse::<TeamKey>().unwrap().as_str(), "frc254B");
+    }
+
+    #[test]
+    fn team_key_rejects_malformed_input() {
+        for s in &["", "frc", "frc0", "frc25x4", "frc254bc", "team254", "-1"] {
+            assert!(s.parse::<TeamKey>().is_err(), "{} parsed", s);
+        }
+    }
+
+    #[test]
+    fn team_keys_order_numerically() {
+        let mut keys: Vec<TeamKey> = vec!["frc1114", "frc254", "frc254B", "frc33"].into_iter().map(|s| s.parse().unwrap()).collect();
+        keys.sort();
+        let keys: Vec<&str> = keys.iter().map(|k| k.as_str()).collect();
+        assert_eq!(keys, vec!["frc33", "frc254", "frc254B", "frc1114"]);
+    }
+
+    #[test]
+    fn event_key_parses() {
+        let key: EventKey = "2019MIKET".parse().unwrap();
+        assert_eq!(key.as_str(), "2019miket");
+        assert_eq!(key.year(), 2019);
+        assert_eq!(key.event_code(), "miket");
+        for s in &["", "2019", "19miket", "2019mi-ket", "201émiket"] {
+            assert!(s.parse::<EventKey>().is_err(), "{} parsed", s);
+        }
+    }
+
+    #[test]
+    fn match_key_parses_levels_sets_and_numbers() {
+        let key: MatchKey = "2019miket_qm12".parse().unwrap();
+        assert_eq!(key.event().as_str(), "2019miket");
+        assert_eq!(key.comp_level(), CompLevel::QualificationMatch);
+        assert_eq!((key.set_number(), key.match_number()), (1, 12));
+
+        let key: MatchKey = "2023mimil_sf13m1".parse().unwrap();
+        assert_eq!(key.comp_level(), CompLevel::SemiFinal);
+        assert_eq!((key.set_number(), key.match_number()), (13, 1));
+        assert_eq!(key.year(), 2023);
+
+        for s in &["2019miket", "2019miket_", "2019miket_qm", "2019miket_xx1", "2019miket_sf1", "2019miket_f1m"] {
+            assert!(s.parse::<MatchKey>().is_err(), "{} parsed", s);
+        }
+    }
+
+    #[test]
+    fn keys_round_trip_through_serde() {
+        let team: TeamKey = "frc254".parse().unwrap();
+        let event: EventKey = "2019miket".parse().unwrap();
+        let m: MatchKey = "2019miket_sf2m3".parse().unwrap();
+        assert_eq!(serde_json::to_string(&team).unwrap(), "\"frc254\"");
+        assert_eq!(serde_json::from_str::<TeamKey>("\"frc254\"").unwrap(), team);
+        assert_eq!(serde_json::from_str::<EventKey>(&serde_json::to_string(&event).unwrap()).unwrap(), event);
+        assert_eq!(serde_json::from_str::<MatchKey>(&serde_json::to_string(&m).unwrap()).unwrap(), m);
+    }
+
+    #[test]
+    fn malformed_team_keys_from_tba_are_kept() {
+        let key: TeamKey = serde_json::from_str("\"frc0\"").unwrap();
+        assert_eq!(key.as_str(), "frc0");
+        assert_eq!(key.number(), 0);
+        assert!(serde_json::from_str::<EventKey>("\"bad\"").is_err());
+        assert!(serde_json::from_str::<MatchKey>("\"2019miket_zz1\"").is_err());
+    }
+}
@@ -15,6 +15,7 @@ extern crate http;
 extern crate serde_cbor;
 extern crate smallvec;
 extern crate time;
+extern crate chrono_tz;
 
 use futures::future;
 use hyper_rustls::HttpsConnector;
@@ -28,6 +29,8 @@ pub mod matches;
 pub mod team;
 pub mod event;
 pub mod district;
+pub mod award;
+pub mod keys;
 mod cache;
 
 use crate::cache::ToInternal;
@@ -1,10 +1,12 @@
 use crate::team::Alliances;
 use crate::TBA;
 use std::cmp::Ordering;
+use chrono::{DateTime, Duration, TimeZone};
 use futures::future;
 use crate::Error;
+use crate::keys::{EventKey, MatchKey, TeamKey};
 
-#[derive(Serialize, Deserialize, Debug, Ord, PartialOrd, Eq, PartialEq, Clone, Copy)]
+#[derive(Serialize, Deserialize, Debug, Ord, PartialOrd, Eq, PartialEq, Clone, Copy, Hash)]
 pub enum CompLevel {
     #[serde(rename = "qm")]
     QualificationMatch,
@@ -408,6 +410,28 @@ pub struct ScoreBreakdown2019 {
     pub red: ScoreBreakdown2019Alliance
 }
 
+/// The fields every season since 2020 has in common. Game specific fields are ignored.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ScoreBreakdownOtherAlliance {
+    #[serde(rename = "autoPoints", default)]
+    pub auto_points: i32,
+    #[serde(rename = "foulPoints", default)]
+    pub foul_points: i32,
+    #[serde(rename = "teleopPoints", default)]
+    pub teleop_points: i32,
+    #[serde(rename = "totalPoints", default)]
+    pub total_points: i32,
+    /// Ranking points earned, including those for the win. Missing from playoff matches.
+    #[serde(default)]
+    pub rp: Option<i32>
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ScoreBreakdownOther {
+    pub blue: ScoreBreakdownOtherAlliance,
+    pub red: ScoreBreakdownOtherAlliance
+}
+
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(untagged)]
@@ -416,24 +440,90 @@ pub enum ScoreBreakdown {
     Year2016(Box<ScoreBreakdown2016>),
     Year2017(Box<ScoreBreakdown2017>),
     Year2018(Box<ScoreBreakdown2018>),
-    Year2019(Box<ScoreBreakdown2019>)
+    Year2019(Box<ScoreBreakdown2019>),
+    /// Any later season. Tried last, since it matches any breakdown with red and blue halves.
+    Other(Box<ScoreBreakdownOther>)
+}
+
+/// One alliance's half of a `ScoreBreakdown`.
+#[derive(Debug, Clone, Copy)]
+pub enum ScoreBreakdownAlliance<'a> {
+    Year2015(&'a ScoreBreakdown2015Alliance),
+    Year2016(&'a ScoreBreakdown2016Alliance),
+    Year2017(&'a ScoreBreakdown2017Alliance),
+    Year2018(&'a ScoreBreakdown2018Alliance),
+    Year2019(&'a ScoreBreakdown2019Alliance),
+    Other(&'a ScoreBreakdownOtherAlliance)
+}
+
+impl<'a> ScoreBreakdownAlliance<'a> {
+    pub fn total_points(&self) -> i32 {
+        match self {
+            ScoreBreakdownAlliance::Year2015(d) => d.total_points,
+            ScoreBreakdownAlliance::Year2016(d) => d.total_points,
+            ScoreBreakdownAlliance::Year2017(d) => d.total_points,
+            ScoreBreakdownAlliance::Year2018(d) => d.total_points,
+            ScoreBreakdownAlliance::Year2019(d) => d.total_points,
+            ScoreBreakdownAlliance::Other(d) => d.total_points,
+        }
+    }
+
+    pub fn auto_points(&self) -> i32 {
+        match self {
+            ScoreBreakdownAlliance::Year2015(d) => d.auto_points,
+            ScoreBreakdownAlliance::Year2016(d) => d.auto_points,
+            ScoreBreakdownAlliance::Year2017(d) => d.auto_points,
+            ScoreBreakdownAlliance::Year2018(d) => d.auto_points,
+            ScoreBreakdownAlliance::Year2019(d) => d.auto_points,
+            ScoreBreakdownAlliance::Other(d) => d.auto_points,
+        }
+    }
+
+    pub fn teleop_points(&self) -> i32 {
+        match self {
+            ScoreBreakdownAlliance::Year2015(d) => d.teleop_points,
+            ScoreBreakdownAlliance::Year2016(d) => d.teleop_points,
+            ScoreBreakdownAlliance::Year2017(d) => d.teleop_points,
+            ScoreBreakdownAlliance::Year2018(d) => d.teleop_points,
+            ScoreBreakdownAlliance::Year2019(d) => d.teleop_points,
+            ScoreBreakdownAlliance::Other(d) => d.teleop_points,
+        }
+    }
+
+    /// Points awarded to this alliance for fouls committed by its opponent.
+    pub fn foul_points(&self) -> i32 {
+        match self {
+            ScoreBreakdownAlliance::Year2015(d) => d.foul_points,
+            ScoreBreakdownAlliance::Year2016(d) => d.foul_points,
+            ScoreBreakdownAlliance::Year2017(d) => d.foul_points,
+            ScoreBreakdownAlliance::Year2018(d) => d.foul_points,
+            ScoreBreakdownAlliance::Year2019(d) => d.foul_points,
+            ScoreBreakdownAlliance::Other(d) => d.foul_points,
+        }
+    }
 }
 
 impl ScoreBreakdown {
-    pub fn total_points(&self, red: bool) -> i32 {
-        match (self, red) {
-            (ScoreBreakdown::Year2015(ref d), true)  => d.red.total_points,
-            (ScoreBreakdown::Year2015(ref d), false) => d.blue.total_points,
-            (ScoreBreakdown::Year2016(ref d), true)  => d.red.total_points,
-            (ScoreBreakdown::Year2016(ref d), false) => d.blue.total_points,
-            (ScoreBreakdown::Year2017(ref d), true)  => d.red.total_points,
-            (ScoreBreakdown::Year2017(ref d), false) => d.blue.total_points,
-            (ScoreBreakdown::Year2018(ref d), true)  => d.red.total_points,
-            (ScoreBreakdown::Year2018(ref d), false) => d.blue.total_points,
-            (ScoreBreakdown::Year2019(ref d), true)  => d.red.total_points,
-            (ScoreBreakdown::Year2019(ref d), false) => d.blue.total_points,
+    pub fn alliance(&self, color: AllianceColor) -> ScoreBreakdownAlliance<'_> {
+        match (self, color) {
+            (ScoreBreakdown::Year2015(ref d), AllianceColor::Red)  => ScoreBreakdownAlliance::Year2015(&d.red),
+            (ScoreBreakdown::Year2015(ref d), AllianceColor::Blue) => ScoreBreakdownAlliance::Year2015(&d.blue),
+            (ScoreBreakdown::Year2016(ref d), AllianceColor::Red)  => ScoreBreakdownAlliance::Year2016(&d.red),
+            (ScoreBreakdown::Year2016(ref d), AllianceColor::Blue) => ScoreBreakdownAlliance::Year2016(&d.blue),
+            (ScoreBreakdown::Year2017(ref d), AllianceColor::Red)  => ScoreBreakdownAlliance::Year2017(&d.red),
+            (ScoreBreakdown::Year2017(ref d), AllianceColor::Blue) => ScoreBreakdownAlliance::Year2017(&d.blue),
+            (ScoreBreakdown::Year2018(ref d), AllianceColor::Red)  => ScoreBreakdownAlliance::Year2018(&d.red),
+            (ScoreBreakdown::Year2018(ref d), AllianceColor::Blue) => ScoreBreakdownAlliance::Year2018(&d.blue),
+            (ScoreBreakdown::Year2019(ref d), AllianceColor::Red)  => ScoreBreakdownAlliance::Year2019(&d.red),
+            (ScoreBreakdown::Year2019(ref d), AllianceColor::Blue) => ScoreBreakdownAlliance::Year2019(&d.blue),
+            (ScoreBreakdown::Other(ref d), AllianceColor::Red)     => ScoreBreakdownAlliance::Other(&d.red),
+            (ScoreBreakdown::Other(ref d), AllianceColor::Blue)    => ScoreBreakdownAlliance::Other(&d.blue),
         }
     }
+
+    pub fn total_points(&self, color: AllianceColor) -> i32 {
+        self.alliance(color).total_points()
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -451,7 +541,27 @@ pub struct Video {
     pub video_type: VideoType
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum AllianceColor {
+    #[serde(rename = "red")]
+    Red,
+    #[serde(rename = "blue")]
+    Blue
+}
+
+impl AllianceColor {
+    pub const BOTH: [AllianceColor; 2] = [AllianceColor::Red, AllianceColor::Blue];
+
+    /// The alliance playing against this one.
+    pub fn opponent(self) -> AllianceColor {
+        match self {
+            AllianceColor::Red => AllianceColor::Blue,
+            AllianceColor::Blue => AllianceColor::Red,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Eq, PartialEq)]
 pub enum Winner {
     #[serde(rename = "red")]
     Red,
@@ -463,13 +573,13 @@ pub enum Winner {
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Match {
-    pub key: String,
+    pub key: MatchKey,
     pub comp_level: CompLevel,
     pub set_number: i32,
     pub match_number: i32,
     pub alliances: Option<Alliances>,
     pub winning_alliance: Option<Winner>,
-    pub event_key: String,
+    pub event_key: EventKey,
     pub time: Option<u64>,
     pub actual_time: Option<u64>,
     pub predicted_time: Option<u64>,
@@ -478,16 +588,90 @@ pub struct Match {
     pub videos: Option<Vec<Video>>
 }
 
+impl Winner {
+    /// The winning alliance, or `None` for a tie.
+    pub fn color(self) -> Option<AllianceColor> {
+        match self {
+            Winner::Red => Some(AllianceColor::Red),
+            Winner::Blue => Some(AllianceColor::Blue),
+            Winner::None => None,
+        }
+    }
+}
+
 impl Match {
-    pub fn from_key(tba: &mut TBA, key: &str) -> impl future::Future<Error = Error, Item = Box<Match>> + Send{
-        tba.get("/match/".to_owned() + key)
+    pub fn from_key(tba: &mut TBA, key: &MatchKey) -> impl future::Future<Error = Error, Item = Box<Match>> + Send{
+        tba.get("/match/".to_owned() + key.as_str())
     }
 
-    pub fn in_event(tba: &mut TBA, key: &str) -> impl future::Future<Error = Error, Item = Vec<Match>> + Send{
-        tba.get("/event/".to_owned() + key + "/matches")
+    pub fn in_event(tba: &mut TBA, key: &EventKey) -> impl future::Future<Error = Error, Item = Vec<Match>> + Send{
+        tba.get("/event/".to_owned() + key.as_str() + "/matches")
     }
 
-    pub fn team_keys(&self) -> Option<Vec<&String>> {
+    /// When the match was scheduled to start.
+    pub fn scheduled_at<T: TimeZone>(&self, tz: &T) -> Option<DateTime<T>> {
+        self.time.map(|t| tz.timestamp(t as i64, 0))
+    }
+
+    /// When the match actually started, if it has been played.
+    pub fn played_at<T: TimeZone>(&self, tz: &T) -> Option<DateTime<T>> {
+        self.actual_time.map(|t| tz.timestamp(t as i64, 0))
+    }
+
+    /// When TBA currently expects the match to start.
+    pub fn predicted_at<T: TimeZone>(&self, tz: &T) -> Option<DateTime<T>> {
+        self.predicted_time.map(|t| tz.timestamp(t as i64, 0))
+    }
+
+    /// The best known start time: actual if played, otherwise predicted, otherwise scheduled.
+    pub fn start_time<T: TimeZone>(&self, tz: &T) -> Option<DateTime<T>> {
+        self.actual_time.or(self.predicted_time).or(self.time)
+            .map(|t| tz.timestamp(t as i64, 0))
+    }
+
+    /// How far behind schedule the match started. Negative if it ran early.
+    pub fn schedule_delay(&self) -> Option<Duration> {
+        match (self.actual_time, self.time) {
+            (Some(actual), Some(scheduled)) => Some(Duration::seconds(actual as i64 - scheduled as i64)),
+            _ => None,
+        }
+    }
+
+    /// Whether this is a match from a double elimination bracket.
+    ///
+    /// TBA numbers double elimination bracket matches as semifinal sets (`sf1m1` through `sf13m1`),
+    /// which is the only format in use since 2023. Older off-season brackets are recognized by
+    /// having more than the two semifinal sets a best-of-three bracket can have.
+    pub fn is_double_elimination(&self) -> bool {
+        self.comp_level == CompLevel::SemiFinal && (self.key.year() >= 2023 || self.set_number > 2)
+    }
+
+    /// Sort key for the order matches are played in.
+    ///
+    /// Best-of-three brackets interleave sets (`qf1m1`, `qf2m1`, ..., `qf1m2`), so matches sort by
+    /// match number before set number. Double elimination brackets play each set in turn, so
+    /// there the set number comes first.
+    pub fn play_order(&self) -> (CompLevel, i32, i32) {
+        if self.is_double_elimination() {
+            (self.comp_level, self.set_number, self.match_number)
+        } else {
+            (self.comp_level, self.match_number, self.set_number)
+        }
+    }
+
+    /// Orders matches by when they were played, or are scheduled to be played if they have
+    /// not been yet. Matches without any time sort last, in play order.
+    pub fn cmp_by_time(&self, other: &Match) -> Ordering {
+        let time = |m: &Match| m.actual_time.or(m.time);
+        match (time(self), time(other)) {
+            (Some(a), Some(b)) => a.cmp(&b).then_with(|| self.cmp(other)),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => self.cmp(other),
+        }
+    }
+
+    pub fn team_keys(&self) -> Option<Vec<&TeamKey>> {
         if let Some(ref alliances) = self.alliances {
             let mut ret = Vec::new();
             ret.extend(&alliances.red.team_keys[..]);
@@ -497,7 +681,30 @@ impl Match {
         None
     }
 
-    pub fn team_keys_mut(&mut self) -> Option<Vec<&mut String>> {
+    /// Which alliance `team` played on in this match, if any.
+    pub fn alliance_of(&self, team: &TeamKey) -> Option<AllianceColor> {
+        let alliances = self.alliances.as_ref()?;
+        AllianceColor::BOTH.iter().cloned()
+            .find(|&c| alliances.get(c).team_keys.iter().any(|t| t == team))
+    }
+
+    /// The alliance playing against `team` in this match, if any.
+    pub fn opponent_of(&self, team: &TeamKey) -> Option<AllianceColor> {
+        self.alliance_of(team).map(AllianceColor::opponent)
+    }
+
+    /// The final score of an alliance, or `None` if the match has not been played.
+    pub fn score_for(&self, color: AllianceColor) -> Option<i32> {
+        self.alliances.as_ref()
+            .map(|a| a.get(color).score)
+            .filter(|&s| s >= 0)
+    }
+
+    pub fn breakdown_for(&self, color: AllianceColor) -> Option<ScoreBreakdownAlliance<'_>> {
+        self.score_breakdown.as_ref().map(|b| b.alliance(color))
+    }
+
+    pub fn team_keys_mut(&mut self) -> Option<Vec<&mut TeamKey>> {
         if let Some(ref mut alliances) = self.alliances {
             let mut ret = Vec::new();
             ret.extend(&mut alliances.blue.team_keys[..]);
@@ -520,29 +727,27 @@ impl Eq for Match {
 
 impl PartialOrd<Match> for Match {
     fn partial_cmp(&self, other: &Match) -> Option<Ordering> {
-        Some(match self.comp_level.cmp(&other.comp_level) {
-            Ordering::Less => Ordering::Less,
-            Ordering::Equal => self.match_number.cmp(&other.match_number),
-            Ordering::Greater => Ordering::Greater,
-        })
+        Some(self.cmp(other))
     }
 }
 
 impl Ord for Match {
+    /// Orders matches the way they are played at an event; see `Match::play_order`.
     fn cmp(&self, other: &Self) -> Ordering {
-        self.partial_cmp(other).unwrap()
+        self.play_order().cmp(&other.play_order())
+            .then_with(|| self.key.as_str().cmp(other.key.as_str()))
     }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct MatchSimple {
-    pub key: String,
+    pub key: MatchKey,
     pub comp_level: CompLevel,
     pub set_number: i32,
     pub match_number: i32,
     pub alliances: Option<Alliances>,
     pub winning_alliance: Option<Winner>,
-    pub event_key: String,
+    pub event_key: EventKey,
     pub time: Option<u64>,
     pub predicted_time: Option<u64>,
     pub actual_time: Option<u64>
@@ -556,4 +761,77 @@ impl MatchSimple {
 //    pub fn from_event(tba: TBA, key:: String,) -> impl future::Future<Error = futures::future::SharedError<Error>, Item = futures::future::SharedItem<Vec<MatchSimple>>> + Send{
 //        tba.get("/event/".to_owned() + &key + "/matches/simple")
 //    }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn later_seasons_parse_as_other_breakdowns() {
+        let breakdown: ScoreBreakdown = serde_json::from_value(serde_json::json!({
+            "red": { "autoPoints": 12, "teleopPoints": 40, "foulPoints": 5, "totalPoints": 57, "rp": 4, "coralBonusAchieved": true },
+            "blue": { "autoPoints": 8, "teleopPoints": 30, "foulPoints": 0, "totalPoints": 38, "rp": 0, "bargeBonusAchieved": false },
+        })).unwrap();
+        match breakdown.alliance(AllianceColor::Red) {
+            ScoreBreakdownAlliance::Other(d) => assert_eq!((d.total_points, d.rp), (57, Some(4))),
+            other => panic!("parsed as {:?}", other),
+        }
+        assert_eq!(breakdown.alliance(AllianceColor::Blue).foul_points(), 0);
+    }
+
+    fn unplayed(key: &str, time: Option<u64>) -> Match {
+        let key: MatchKey = key.parse().unwrap();
+        serde_json::from_value(serde_json::json!({
+            "key": key.as_str(),
+            "comp_level": match key.comp_level() {
+                CompLevel::QualificationMatch => "qm",
+                CompLevel::EighthFinal => "ef",
+                CompLevel::QuarterFinal => "qf",
+                CompLevel::SemiFinal => "sf",
+                CompLevel::Final => "f",
+            },
+            "set_number": key.set_number(),
+            "match_number": key.match_number(),
+            "event_key": key.event().as_str(),
+            "time": time,
+        })).unwrap()
+    }
+
+    fn sorted(keys: &[&str]) -> Vec<String> {
+        let mut matches: Vec<Match> = keys.iter().map(|k| unplayed(k, None)).collect();
+        matches.sort();
+        matches.iter().map(|m| m.key.to_string()).collect()
+    }
+
+    #[test]
+    fn best_of_three_sets_interleave() {
+        assert_eq!(
+            sorted(&["2019miket_qf2m1", "2019miket_qf1m2", "2019miket_f1m1", "2019miket_qf1m1", "2019miket_qm10", "2019miket_qm9"]),
+            vec!["2019miket_qm9", "2019miket_qm10", "2019miket_qf1m1", "2019miket_qf2m1", "2019miket_qf1m2", "2019miket_f1m1"],
+        );
+    }
+
+    #[test]
+    fn double_elimination_sets_play_in_turn() {
+        assert!(unplayed("2023mimil_sf1m1", None).is_double_elimination());
+        assert!(!unplayed("2019miket_sf1m1", None).is_double_elimination());
+        assert!(unplayed("2019cmptx_sf5m1", None).is_double_elimination());
+        assert_eq!(
+            sorted(&["2023mimil_sf10m1", "2023mimil_sf2m1", "2023mimil_f1m2", "2023mimil_sf1m1", "2023mimil_f1m1"]),
+            vec!["2023mimil_sf1m1", "2023mimil_sf2m1", "2023mimil_sf10m1", "2023mimil_f1m1", "2023mimil_f1m2"],
+        );
+    }
+
+    #[test]
+    fn matches_without_a_time_sort_last() {
+        let mut matches = [
+            unplayed("2019miket_qm3", None),
+            unplayed("2019miket_qm2", Some(200)),
+            unplayed("2019miket_qm1", Some(100)),
+            unplayed("2019miket_qm4", None),
+        ];
+        matches.sort_by(|a, b| a.cmp_by_time(b));
+        let keys: Vec<String> = matches.iter().map(|m| m.key.to_string()).collect();
+        assert_eq!(keys, vec!["2019miket_qm1", "2019miket_qm2", "2019miket_qm3", "2019miket_qm4"]);
+    }
+}
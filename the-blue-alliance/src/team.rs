@@ -1,6 +1,9 @@
 use crate::TBA;
 use crate::district::District;
-use crate::event::Event;
+use crate::event::{AllianceStatus, Event};
+use crate::award::Award;
+use crate::matches::AllianceColor;
+use crate::keys::{EventKey, TeamKey};
 use std::collections::HashMap;
 use futures::future;
 use crate::Error;
@@ -8,9 +11,9 @@ use crate::Error;
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct MatchAlliance {
     pub score: i32,
-    pub team_keys: [String; 3],
-    pub surrogate_team_keys: Option<Vec<String>>,
-    pub dq_team_keys: Option<Vec<String>>,
+    pub team_keys: [TeamKey; 3],
+    pub surrogate_team_keys: Option<Vec<TeamKey>>,
+    pub dq_team_keys: Option<Vec<TeamKey>>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -19,9 +22,30 @@ pub struct Alliances {
     pub blue: MatchAlliance
 }
 
+impl MatchAlliance {
+    /// Whether `team` played this match as a surrogate.
+    pub fn is_surrogate(&self, team: &TeamKey) -> bool {
+        self.surrogate_team_keys.iter().flatten().any(|t| t == team)
+    }
+
+    /// Whether `team` was disqualified from this match.
+    pub fn is_disqualified(&self, team: &TeamKey) -> bool {
+        self.dq_team_keys.iter().flatten().any(|t| t == team)
+    }
+}
+
+impl Alliances {
+    pub fn get(&self, color: AllianceColor) -> &MatchAlliance {
+        match color {
+            AllianceColor::Red => &self.red,
+            AllianceColor::Blue => &self.blue,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Team {
-    pub key: String,
+    pub key: TeamKey,
     pub team_number: i32,
     pub nickname: Option<String>,
     pub city: Option<String>,
@@ -41,8 +65,8 @@ pub struct Team {
 }
 
 impl Team {
-    pub fn from_key(tba: &TBA, key: &str) -> impl future::Future<Error = Error, Item = Box<Team>> + Send{
-        tba.get("/team/".to_owned() + key)
+    pub fn from_key(tba: &TBA, key: &TeamKey) -> impl future::Future<Error = Error, Item = Box<Team>> + Send{
+        tba.get("/team/".to_owned() + key.as_str())
     }
 
     pub fn all(tba: &TBA, page: u32) -> impl future::Future<Error = Error, Item = Vec<Team>> + Send{
@@ -55,18 +79,60 @@ impl Team {
     }
 
     pub fn years_participated(&self, tba: &TBA) -> impl future::Future<Error = Error, Item = Vec<u32>> + Send{
-        tba.get("/team/".to_owned() + &self.key + "/years_participated")
+        tba.get("/team/".to_owned() + self.key.as_str() + "/years_participated")
     }
 
     pub fn districts(&self, tba: &TBA) -> impl future::Future<Error = Error, Item = Vec<District>> + Send{
-        tba.get("/team/".to_owned() + &self.key + "/districts")
+        tba.get("/team/".to_owned() + self.key.as_str() + "/districts")
     }
 
     pub fn events(&self, tba: &TBA) -> impl future::Future<Error = Error, Item = Vec<Event>> + Send{
-        tba.get("/team/".to_owned() + &self.key + "/events")
+        tba.get("/team/".to_owned() + self.key.as_str() + "/events")
+    }
+
+    pub fn events_in_year(&self, tba: &TBA, year: i32) -> impl future::Future<Error = Error, Item = Vec<Event>> + Send{
+        Event::for_team_key_in_year(tba, &self.key, year)
+    }
+
+    pub fn awards_in_year(&self, tba: &TBA, year: i32) -> impl future::Future<Error = Error, Item = Vec<Award>> + Send{
+        Award::for_team_key_in_year(tba, &self.key, year)
+    }
+
+    /// How the team did at each event it attended in `year`. Events that have not started have no status.
+    pub fn event_statuses_in_year(&self, tba: &TBA, year: i32) -> impl future::Future<Error = Error, Item = HashMap<EventKey, Option<TeamEventStatus>>> + Send{
+        assert_eq!(year.to_string().len(), 4);
+        tba.get("/team/".to_owned() + self.key.as_str() + "/events/" + &year.to_string() + "/statuses")
     }
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TeamEventRanking {
+    pub rank: Option<i32>,
+    pub matches_played: Option<i32>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TeamEventQualStatus {
+    pub num_teams: Option<i32>,
+    pub ranking: Option<TeamEventRanking>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TeamEventAllianceStatus {
+    pub name: Option<String>,
+    pub number: i32,
+    /// 0 for the captain, then the order the team was picked in. -1 for a backup robot.
+    pub pick: i32,
+}
+
+/// How a team is doing or did at one event.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TeamEventStatus {
+    pub qual: Option<TeamEventQualStatus>,
+    pub alliance: Option<TeamEventAllianceStatus>,
+    pub playoff: Option<AllianceStatus>,
+}
+
 impl PartialEq<Team> for Team {
     fn eq(&self, other: &Team) -> bool {
         self.key == other.key
@@ -75,7 +141,7 @@ impl PartialEq<Team> for Team {
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct TeamSimple {
-    pub key: String,
+    pub key: TeamKey,
     pub number: i32,
     pub nickname: Option<String>,
     pub city: Option<String>,